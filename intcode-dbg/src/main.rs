@@ -0,0 +1,293 @@
+extern crate intcode;
+use intcode::disasm;
+use intcode::load::parse_program;
+use intcode::{Intcode, IntcodeError, State, WatchKind};
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands:
+  step [n]          run n instructions (default 1)
+  continue          run until a breakpoint, watchpoint, halt or input wait
+  break <addr>      stop before executing addr
+  watch <addr>      stop after addr is written
+  delete <addr>     remove the breakpoint or watchpoint at addr
+  mem <addr> [len]  show len words of memory from addr (default 1, at most 4096)
+  regs              show ip, relative base and state
+  input <n>         queue n as input
+  disasm [addr] [n] list n lines from addr (default: around ip)
+  quit";
+
+// most words a single mem command shows
+const MEM_MAX: usize = 4096;
+
+struct Debugger {
+    intcode: Intcode,
+}
+
+impl Debugger {
+    fn new(program: Vec<i64>) -> Debugger {
+        Debugger { intcode: Intcode::new(program) }
+    }
+
+    /*
+        Runs one instruction and reports it. Returns false when
+        execution should stop here.
+    */
+    fn step(&mut self, out: &mut dyn Write) -> io::Result<bool> {
+        // just the words the instruction can span, not a copy of all memory
+        let ip = self.intcode.get_ip();
+        let window: Vec<i64> = (ip..ip + 4).map_while(|i| self.intcode.get_value_at(i)).collect();
+        let text = match disasm::decode_at(&window, 0) {
+            Some((instr, params)) => disasm::format_instruction(&instr, &params),
+            None => String::new(),
+        };
+
+        let step = match self.intcode.step() {
+            Ok(step) => step,
+            Err(e) => return self.report(Err(e), out),
+        };
+
+        let operands: Vec<String> = step.operands.iter().map(|v| v.to_string()).collect();
+        write!(out, "{:>6}: {}  ({})", step.ip, text, operands.join(", "))?;
+        if let Some(w) = step.write {
+            write!(out, "  [{}] <- {}", w.address, w.value)?;
+        }
+        writeln!(out)?;
+
+        self.report(Ok(step.state), out)
+    }
+
+    /*
+        Prints why execution stopped, if it did. Returns false when it did.
+    */
+    fn report(&mut self, result: Result<State, IntcodeError>, out: &mut dyn Write) -> io::Result<bool> {
+        for value in self.intcode.take_outputs() {
+            writeln!(out, "output: {}", value)?;
+        }
+
+        match result {
+            Ok(State::Halted) => writeln!(out, "halted")?,
+            Ok(State::InputRequired) => writeln!(out, "waiting for input")?,
+            Ok(State::Breakpoint(addr)) => writeln!(out, "breakpoint: {}", addr)?,
+            Ok(State::Watch { addr, old, new }) => writeln!(out, "watchpoint: [{}] {} -> {}", addr, old, new)?,
+            Ok(_) => return Ok(true),
+            Err(e) => writeln!(out, "error: {}", e)?,
+        }
+
+        Ok(false)
+    }
+
+    fn run(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        // process() only moves past a breakpoint it stopped at itself,
+        // not one reached by stepping, so run that instruction here
+        if self.intcode.has_breakpoint(self.intcode.get_ip()) {
+            match self.intcode.step() {
+                Ok(step) if step.state == State::Processing => {}
+                Ok(step) => return self.report(Ok(step.state), out).map(|_| ()),
+                Err(e) => return self.report(Err(e), out).map(|_| ()),
+            }
+        }
+
+        let result = self.intcode.process();
+        self.report(result, out)?;
+        Ok(())
+    }
+
+    fn disasm(&self, start: Option<usize>, count: usize, out: &mut dyn Write) -> io::Result<()> {
+        let ip = self.intcode.get_ip();
+        let start = start.unwrap_or_else(|| ip.saturating_sub(8));
+
+        for line in disasm::disassemble(&self.intcode.memory())
+            .into_iter()
+            .filter(|l| l.address >= start)
+            .take(count)
+        {
+            let marker = if line.address == ip { "=>" } else { "  " };
+            let stop = if self.intcode.has_breakpoint(line.address) { "*" } else { " " };
+            writeln!(out, "{}{}{}", marker, stop, line)?;
+        }
+
+        Ok(())
+    }
+
+    /*
+        Returns false once the user asks to quit.
+    */
+    fn command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args: Vec<Option<i64>> = words.iter().skip(1).map(|w| w.parse::<i64>().ok()).collect();
+        let addr = |i: usize| args.get(i).copied().flatten().filter(|a| *a >= 0).map(|a| a as usize);
+
+        match words.first().copied() {
+            None => {}
+            Some("step") | Some("s") => {
+                let count = addr(0).unwrap_or(1);
+                for _ in 0..count {
+                    if !self.step(out)? {
+                        break;
+                    }
+                }
+            }
+            Some("continue") | Some("c") => self.run(out)?,
+            Some("break") | Some("b") => match addr(0) {
+                Some(a) => {
+                    self.intcode.add_breakpoint(a);
+                    writeln!(out, "breakpoint at {}", a)?;
+                }
+                None => writeln!(out, "usage: break <addr>")?,
+            },
+            Some("watch") | Some("w") => match addr(0) {
+                Some(a) => {
+                    self.intcode.add_watchpoint(a, WatchKind::Write);
+                    writeln!(out, "watching {}", a)?;
+                }
+                None => writeln!(out, "usage: watch <addr>")?,
+            },
+            Some("delete") | Some("d") => match addr(0) {
+                Some(a) => {
+                    self.intcode.remove_breakpoint(a);
+                    self.intcode.remove_watchpoint(a);
+                }
+                None => writeln!(out, "usage: delete <addr>")?,
+            },
+            Some("mem") | Some("m") => match addr(0) {
+                Some(a) => {
+                    let len = addr(1).unwrap_or(1).min(MEM_MAX);
+                    let values: Vec<String> = (a..a.saturating_add(len))
+                        .map(|i| self.intcode.get_value_at(i).unwrap_or(0).to_string())
+                        .collect();
+                    writeln!(out, "{:>6}: {}", a, values.join(" "))?;
+                }
+                None => writeln!(out, "usage: mem <addr> [len]")?,
+            },
+            Some("regs") | Some("r") => {
+                writeln!(
+                    out,
+                    "ip: {}  rb: {}  state: {}",
+                    self.intcode.get_ip(),
+                    self.intcode.get_relative_base(),
+                    self.intcode.get_state()
+                )?;
+            }
+            Some("input") | Some("i") => match args.first().copied().flatten() {
+                Some(n) => self.intcode.add_input(n),
+                None => writeln!(out, "usage: input <n>")?,
+            },
+            Some("disasm") | Some("l") => self.disasm(addr(0), addr(1).unwrap_or(16), out)?,
+            Some("help") | Some("h") => writeln!(out, "{}", HELP)?,
+            Some("quit") | Some("q") => return Ok(false),
+            Some(other) => writeln!(out, "unknown command '{}', try help", other)?,
+        }
+
+        Ok(true)
+    }
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode-dbg <program>");
+            std::process::exit(1);
+        }
+    };
+
+    let text = fs::read_to_string(&path).map_err(|e| e.to_string());
+    let program = match text.and_then(|t| parse_program(&t).map_err(|e| e.to_string())) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut debugger = Debugger::new(program);
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    loop {
+        let _ = write!(out, "(dbg) ");
+        let _ = out.flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        match debugger.command(line.trim(), &mut out) {
+            Ok(true) => {}
+            Ok(false) | Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(debugger: &mut Debugger, line: &str) -> String {
+        let mut out = Vec::new();
+        debugger.command(line, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step_and_regs() {
+        let mut debugger = Debugger::new(vec![1101,2,3,5,99,0]);
+
+        assert_eq!(run(&mut debugger, "step"), "     0: add #2, #3, [5]  (2, 3)  [5] <- 5\n");
+        assert_eq!(run(&mut debugger, "regs"), "ip: 4  rb: 0  state: processing\n");
+        assert_eq!(run(&mut debugger, "mem 4 2"), "     4: 99 5\n");
+        assert_eq!(run(&mut debugger, "mem 0 100000000000").split(' ').filter(|w| !w.is_empty()).count(), 1 + MEM_MAX);
+    }
+
+    #[test]
+    fn breakpoint_and_input() {
+        let mut debugger = Debugger::new(vec![3,9,4,9,1101,0,0,10,99,0,0]);
+        run(&mut debugger, "break 4");
+
+        let out = run(&mut debugger, "continue");
+        assert!(out.ends_with("waiting for input\n"));
+
+        run(&mut debugger, "input 7");
+        let out = run(&mut debugger, "continue");
+        assert_eq!(out, "output: 7\nbreakpoint: 4\n");
+
+        let out = run(&mut debugger, "c");
+        assert_eq!(out, "halted\n");
+    }
+
+    #[test]
+    fn continue_after_stepping_onto_breakpoint() {
+        let mut debugger = Debugger::new(vec![1101,1,1,9,1101,2,2,10,99,0,0]);
+        run(&mut debugger, "break 4");
+        run(&mut debugger, "step");
+
+        assert_eq!(run(&mut debugger, "continue"), "halted\n");
+        assert_eq!(debugger.intcode.get_value_at(10), Some(4));
+    }
+
+    #[test]
+    fn watchpoint_stops_after_write() {
+        let mut debugger = Debugger::new(vec![1101,1,1,9,1101,2,2,10,99,0,0]);
+        run(&mut debugger, "watch 10");
+
+        let out = run(&mut debugger, "continue");
+        assert_eq!(out, "watchpoint: [10] 0 -> 4\n");
+        assert_eq!(debugger.intcode.get_ip(), 8);
+    }
+
+    #[test]
+    fn disasm_marks_ip() {
+        let mut debugger = Debugger::new(vec![1101,2,3,5,99,0]);
+        run(&mut debugger, "step");
+
+        let out = run(&mut debugger, "disasm 0 2");
+        assert_eq!(out, "        0: add #2, #3, [5]\n=>      4: hlt\n");
+    }
+}
@@ -0,0 +1,111 @@
+extern crate intcode;
+use intcode::icb::Image;
+use intcode::load::parse_program;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+const USAGE: &str = "usage: intcode-icb [--entry <addr>] <input> <output>
+
+Converts between comma-separated text programs and .icb images.
+An input ending in .icb is written out as text, anything else is
+read as text and written as an .icb image starting at --entry.";
+
+fn is_icb(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "icb")
+}
+
+fn to_text(words: &[i64]) -> String {
+    let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+    format!("{}\n", words.join(","))
+}
+
+/*
+    Returns a one-line summary of what was written.
+*/
+fn convert(input: &Path, output: &Path, entry: usize) -> Result<String, String> {
+    if is_icb(input) {
+        let file = File::open(input).map_err(|e| e.to_string())?;
+        let image = Image::read(BufReader::new(file)).map_err(|e| e.to_string())?;
+        fs::write(output, to_text(&image.words)).map_err(|e| e.to_string())?;
+
+        let mut summary = format!("{} words", image.words.len());
+        if image.entry != 0 {
+            summary.push_str(&format!(", entry point {} dropped", image.entry));
+        }
+        return Ok(summary);
+    }
+
+    let text = fs::read_to_string(input).map_err(|e| e.to_string())?;
+    let words = parse_program(&text).map_err(|e| e.to_string())?;
+    let image = Image { words, entry };
+
+    let file = File::create(output).map_err(|e| e.to_string())?;
+    image.write(BufWriter::new(file)).map_err(|e| e.to_string())?;
+
+    Ok(format!("{} words, {} bytes", image.words.len(), image.to_bytes().len()))
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut entry = 0;
+
+    if let Some(i) = args.iter().position(|a| a == "--entry") {
+        match args.get(i + 1).and_then(|a| a.parse::<usize>().ok()) {
+            Some(addr) => entry = addr,
+            None => {
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+        args.drain(i..i + 2);
+    }
+
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+
+    let (input, output) = (Path::new(&args[0]), Path::new(&args[1]));
+    match convert(input, output, entry) {
+        Ok(summary) => println!("{} -> {}: {}", input.display(), output.display(), summary),
+        Err(e) => {
+            eprintln!("{}: {}", input.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("intcode-icb-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn text_to_icb_and_back() {
+        let (text, icb, back) = (temp("in.txt"), temp("prog.icb"), temp("out.txt"));
+        fs::write(&text, "1002,4,3,4,\n33,\n").unwrap();
+
+        assert_eq!(convert(&text, &icb, 0), Ok("5 words, 12 bytes".to_string()));
+        assert_eq!(convert(&icb, &back, 0), Ok("5 words".to_string()));
+        assert_eq!(fs::read_to_string(&back).unwrap(), "1002,4,3,4,33\n");
+
+        for path in [text, icb, back] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let (text, icb) = (temp("bad.txt"), temp("bad.icb"));
+        fs::write(&text, "1,2,\n3,x").unwrap();
+
+        assert_eq!(convert(&text, &icb, 0), Err("line 2, column 3: not a number: 'x'".to_string()));
+        fs::remove_file(text).unwrap();
+    }
+}
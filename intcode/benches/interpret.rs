@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use intcode::jit::Jit;
+use intcode::{asm, Intcode};
+
+/*
+    Interpreter and jit throughput on a long-running loop, with the
+    interpreter measured both with and without its decode cache. Run with
+
+        cargo bench --bench interpret
+
+    Each iteration of the loop executes five instructions and
+    mixes position and immediate operands.
+*/
+
+const PROGRAM: &str = "
+        in [n]
+loop:   add [i], #1, [i]
+        mul [i], [i], [sq]
+        add [sum], [sq], [sum]
+        lt [i], [n], [t]
+        jt [t], #loop
+        out [sum]
+        hlt
+i:      db 0
+n:      db 0
+sq:     db 0
+sum:    db 0
+t:      db 0
+";
+
+const ITERATIONS: i64 = 2_000_000;
+const RUNS: usize = 5;
+
+#[derive(Clone, Copy)]
+enum Engine {
+    Cached,
+    Uncached,
+    Jit,
+}
+
+fn run(program: &[i64], engine: Engine) -> Duration {
+    let mut intcode = Intcode::new(program.to_vec());
+    intcode.set_decode_cache(!matches!(engine, Engine::Uncached));
+    intcode.add_input(ITERATIONS);
+
+    let start = Instant::now();
+    let output = if let Engine::Jit = engine {
+        let mut jit = Jit::new(intcode);
+        jit.process().unwrap();
+        jit.intcode().get_output()
+    } else {
+        intcode.process().unwrap();
+        intcode.get_output()
+    };
+    let elapsed = start.elapsed();
+
+    let expected: i64 = (1..=ITERATIONS).map(|i| i * i).sum();
+    assert_eq!(output, Some(expected));
+
+    elapsed
+}
+
+fn main() {
+    let program = asm::assemble(PROGRAM).unwrap();
+    let instructions = ITERATIONS as f64 * 5.0;
+
+    for (name, engine) in [("cached", Engine::Cached), ("uncached", Engine::Uncached), ("jit", Engine::Jit)] {
+        let best = (0..RUNS).map(|_| run(&program, engine)).min().unwrap();
+
+        println!(
+            "{:>8}: {} loop iterations, best of {} runs {:?}, {:.1} ns/instruction",
+            name,
+            ITERATIONS,
+            RUNS,
+            best,
+            best.as_nanos() as f64 / instructions
+        );
+    }
+}
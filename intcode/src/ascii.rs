@@ -0,0 +1,173 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{Intcode, IntcodeError, State};
+
+/*
+    Talks to programs that speak ASCII: lines of text go in as character
+    codes followed by a newline, and output codes up to 127 come back as
+    a String. Anything outside that range, like a final score, is kept
+    as a number instead.
+*/
+pub struct Ascii {
+    intcode: Intcode,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Response {
+    pub text: String,
+    pub values: Vec<i64>,
+    pub state: State,
+}
+
+impl Ascii {
+    pub fn new(intcode: Intcode) -> Ascii {
+        Ascii { intcode }
+    }
+
+    pub fn intcode(&self) -> &Intcode {
+        &self.intcode
+    }
+
+    pub fn intcode_mut(&mut self) -> &mut Intcode {
+        &mut self.intcode
+    }
+
+    pub fn into_inner(self) -> Intcode {
+        self.intcode
+    }
+
+    /*
+        Queues line as input, with a newline added.
+    */
+    pub fn send(&mut self, line: &str) {
+        self.intcode.add_inputs(line.chars().map(|c| c as i64));
+        self.intcode.add_input(10);
+    }
+
+    /*
+        Runs until the program stops and collects what it printed.
+    */
+    pub fn run(&mut self) -> Result<Response, IntcodeError> {
+        let state = self.intcode.process()?;
+        let mut text = String::new();
+        let mut values = Vec::new();
+
+        for value in self.intcode.take_outputs() {
+            match value {
+                0..=127 => text.push(value as u8 as char),
+                _ => values.push(value),
+            }
+        }
+
+        Ok(Response { text, values, state })
+    }
+
+    pub fn command(&mut self, line: &str) -> Result<Response, IntcodeError> {
+        self.send(line);
+        self.run()
+    }
+
+    /*
+        Prints everything the program outputs to output and answers its
+        prompts with lines read from input, until it halts, stops for
+        another reason, or input runs out. Numbers outside the ASCII
+        range are printed on lines of their own.
+    */
+    pub fn interact<R, W>(&mut self, mut input: R, mut output: W) -> io::Result<State>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        loop {
+            let response = self.run().map_err(io::Error::other)?;
+
+            output.write_all(response.text.as_bytes())?;
+            for value in &response.values {
+                writeln!(output, "{}", value)?;
+            }
+            output.flush()?;
+
+            if response.state != State::InputRequired {
+                return Ok(response.state);
+            }
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(response.state);
+            }
+            self.send(line.trim_end_matches(['\n', '\r']));
+        }
+    }
+
+    /*
+        interact on the terminal.
+    */
+    pub fn play(&mut self) -> io::Result<State> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+
+        self.interact(stdin.lock(), stdout.lock())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    // prints a prompt, echoes one line back and outputs its length
+    const ECHO: &str = "
+        out #62
+        out #32
+loop:   in [c]
+        out [c]
+        add [n], #1, [n]
+        eq [c], #10, [t]
+        jf [t], #loop
+        add [n], #999, [n]
+        out [n]
+        hlt
+c:      db 0
+n:      db 0
+t:      db 0
+";
+
+    fn echo() -> Ascii {
+        Ascii::new(Intcode::new(asm::assemble(ECHO).unwrap()))
+    }
+
+    #[test]
+    fn prompt_then_command() {
+        let mut ascii = echo();
+
+        let prompt = ascii.run().unwrap();
+        assert_eq!(prompt, Response { text: "> ".to_string(), values: vec![], state: State::InputRequired });
+
+        let reply = ascii.command("north").unwrap();
+        assert_eq!(reply.text, "north\n");
+        assert_eq!(reply.values, vec![1005]);
+        assert_eq!(reply.state, State::Halted);
+    }
+
+    #[test]
+    fn interactive_session() {
+        let mut ascii = echo();
+        let mut output = Vec::new();
+
+        let state = ascii.interact(&b"take key\n"[..], &mut output).unwrap();
+
+        assert_eq!(state, State::Halted);
+        assert_eq!(String::from_utf8(output).unwrap(), "> take key\n1008\n");
+    }
+
+    #[test]
+    fn input_running_out() {
+        let mut ascii = echo();
+        let mut output = Vec::new();
+
+        let state = ascii.interact(&b""[..], &mut output).unwrap();
+
+        assert_eq!(state, State::InputRequired);
+        assert_eq!(output, b"> ");
+    }
+}
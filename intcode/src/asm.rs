@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::Instruction;
+
+/*
+    A small assembly language for Intcode, the inverse of disasm.
+
+        ; comments run to the end of the line
+        start:  in [x]                  ; labels end in ':'
+                mul [x], #2, rb+1       ; [a] position, #a immediate, rb+a relative
+                jt #1, #start
+        x:      db 0                    ; data words, 'data' works too
+        size = x + 1                    ; named constants
+
+    Operands are constant expressions over numbers, labels, constants
+    and $ (the address of the current line), with + - * and parentheses.
+*/
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+fn error<T>(line: usize, message: String) -> Result<T, AsmError> {
+    Err(AsmError { line, message })
+}
+
+enum Statement {
+    Instruction(String, Vec<(char, String)>),
+    Data(Vec<String>),
+}
+
+struct Entry {
+    line: usize,
+    address: usize,
+    statement: Statement,
+}
+
+fn param_count(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "add" | "mul" | "lt" | "eq" => Some(3),
+        "jt" | "jf" => Some(2),
+        "in" | "out" | "rbo" => Some(1),
+        "hlt" => Some(0),
+        _ => None,
+    }
+}
+
+fn build(mnemonic: &str, m: &[char]) -> Instruction {
+    match mnemonic {
+        "add" => Instruction::Add(m[0], m[1], m[2]),
+        "mul" => Instruction::Multiply(m[0], m[1], m[2]),
+        "in" => Instruction::Input(m[0]),
+        "out" => Instruction::Output(m[0]),
+        "jt" => Instruction::JumpIfTrue(m[0], m[1]),
+        "jf" => Instruction::JumpIfFalse(m[0], m[1]),
+        "lt" => Instruction::LessThan(m[0], m[1], m[2]),
+        "eq" => Instruction::Equals(m[0], m[1], m[2]),
+        "rbo" => Instruction::AdjustRelativeBase(m[0]),
+        _ => Instruction::Halt,
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/*
+    Splits an operand into its mode and expression text.
+*/
+fn parse_operand(line: usize, operand: &str) -> Result<(char, String), AsmError> {
+    if let Some(rest) = operand.strip_prefix('#') {
+        return Ok(('1', rest.trim().to_string()));
+    }
+
+    if operand.starts_with('[') && operand.ends_with(']') {
+        return Ok(('0', operand[1..operand.len() - 1].trim().to_string()));
+    }
+
+    if let Some(rest) = operand.strip_prefix("rb") {
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Ok(('2', "0".to_string()));
+        }
+        if rest.starts_with('+') || rest.starts_with('-') {
+            return Ok(('2', format!("0{}", rest)));
+        }
+    }
+
+    error(line, format!("operand '{}' needs a mode: [a], #a or rb+a", operand))
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    text.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels: HashMap<String, i64> = HashMap::new();
+    let mut constants: HashMap<String, (usize, String)> = HashMap::new();
+    let mut entries = Vec::new();
+    let mut address = 0;
+
+    for (idx, raw) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut text = match raw.find(';') {
+            Some(pos) => &raw[..pos],
+            None => raw,
+        }
+        .trim();
+
+        if let Some(pos) = text.find('=') {
+            let name = text[..pos].trim();
+            if !is_identifier(name) {
+                return error(line, format!("bad constant name '{}'", name));
+            }
+            if labels.contains_key(name) || constants.contains_key(name) {
+                return error(line, format!("'{}' is already defined", name));
+            }
+            constants.insert(name.to_string(), (line, text[pos + 1..].trim().to_string()));
+            continue;
+        }
+
+        while let Some(pos) = text.find(':') {
+            let name = text[..pos].trim();
+            if !is_identifier(name) {
+                return error(line, format!("bad label '{}'", name));
+            }
+            if labels.contains_key(name) || constants.contains_key(name) {
+                return error(line, format!("'{}' is already defined", name));
+            }
+            labels.insert(name.to_string(), address as i64);
+            text = text[pos + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match text.find(char::is_whitespace) {
+            Some(pos) => (&text[..pos], text[pos..].trim()),
+            None => (text, ""),
+        };
+        let mnemonic = mnemonic.to_lowercase();
+
+        let statement = if mnemonic == "db" || mnemonic == "data" {
+            if rest.is_empty() {
+                return error(line, format!("{} needs at least one value", mnemonic));
+            }
+            Statement::Data(split_list(rest))
+        } else {
+            let count = match param_count(&mnemonic) {
+                Some(count) => count,
+                None => return error(line, format!("unknown mnemonic '{}'", mnemonic)),
+            };
+
+            let operands = if rest.is_empty() { Vec::new() } else { split_list(rest) };
+            if operands.len() != count {
+                return error(line, format!("{} takes {} operands, got {}", mnemonic, count, operands.len()));
+            }
+
+            let mut parsed = Vec::new();
+            for operand in operands {
+                parsed.push(parse_operand(line, &operand)?);
+            }
+
+            if build(&mnemonic, &parsed.iter().map(|p| p.0).collect::<Vec<char>>()).writes()
+                && parsed.last().map(|p| p.0) == Some('1')
+            {
+                return error(line, format!("{} can't write to an immediate operand", mnemonic));
+            }
+
+            Statement::Instruction(mnemonic, parsed)
+        };
+
+        let size = match &statement {
+            Statement::Instruction(_, operands) => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        };
+
+        entries.push(Entry { line, address, statement });
+        address += size;
+    }
+
+    let eval = Evaluator { labels: &labels, constants: &constants };
+    let mut program = Vec::with_capacity(address);
+
+    for entry in entries {
+        match entry.statement {
+            Statement::Instruction(mnemonic, operands) => {
+                let modes: Vec<char> = operands.iter().map(|o| o.0).collect();
+                program.push(build(&mnemonic, &modes).encode());
+
+                for (_, expr) in operands {
+                    program.push(eval.evaluate(entry.line, &expr, entry.address, &mut Vec::new())?);
+                }
+            }
+            Statement::Data(values) => {
+                for expr in values {
+                    program.push(eval.evaluate(entry.line, &expr, entry.address, &mut Vec::new())?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Here,
+    Op(char),
+}
+
+fn tokenize(line: usize, expr: &str) -> Result<Vec<Token>, AsmError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            match text.parse::<i64>() {
+                Ok(n) => tokens.push(Token::Number(n)),
+                Err(_) => return error(line, format!("number '{}' is too large", text)),
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if c == '$' {
+            tokens.push(Token::Here);
+            i += 1;
+        } else if "+-*()".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return error(line, format!("unexpected '{}' in '{}'", c, expr));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Evaluator<'a> {
+    labels: &'a HashMap<String, i64>,
+    constants: &'a HashMap<String, (usize, String)>,
+}
+
+struct Parser<'a, 'b> {
+    eval: &'b Evaluator<'a>,
+    line: usize,
+    here: usize,
+    tokens: Vec<Token>,
+    pos: usize,
+    // constants being evaluated, to catch definitions that refer to themselves
+    stack: &'b mut Vec<String>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn evaluate(&self, line: usize, expr: &str, here: usize, stack: &mut Vec<String>) -> Result<i64, AsmError> {
+        let tokens = tokenize(line, expr)?;
+        if tokens.is_empty() {
+            return error(line, "missing expression".to_string());
+        }
+
+        let mut parser = Parser { eval: self, line, here, tokens, pos: 0, stack };
+        let value = parser.sum()?;
+
+        if parser.pos < parser.tokens.len() {
+            return error(line, format!("trailing input in '{}'", expr));
+        }
+
+        Ok(value)
+    }
+
+    fn lookup(&self, line: usize, name: &str, here: usize, stack: &mut Vec<String>) -> Result<i64, AsmError> {
+        if let Some(value) = self.labels.get(name) {
+            return Ok(*value);
+        }
+
+        match self.constants.get(name) {
+            Some((def_line, expr)) => {
+                if stack.iter().any(|s| s == name) {
+                    return error(*def_line, format!("constant '{}' refers to itself", name));
+                }
+                stack.push(name.to_string());
+                let value = self.evaluate(*def_line, expr, here, stack);
+                stack.pop();
+                value
+            }
+            None => error(line, format!("undefined name '{}'", name)),
+        }
+    }
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(c)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn overflow<T>(&self) -> Result<T, AsmError> {
+        error(self.line, "expression overflows".to_string())
+    }
+
+    fn sum(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.product()?;
+
+        while let Some(op) = self.peek_op() {
+            if op != '+' && op != '-' {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = match if op == '+' { value.checked_add(rhs) } else { value.checked_sub(rhs) } {
+                Some(v) => v,
+                None => return self.overflow(),
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.unary()?;
+
+        while self.peek_op() == Some('*') {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match value.checked_mul(rhs) {
+                Some(v) => v,
+                None => return self.overflow(),
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, AsmError> {
+        match self.peek_op() {
+            Some('-') => {
+                self.pos += 1;
+                let value = self.unary()?;
+                match value.checked_neg() {
+                    Some(v) => Ok(v),
+                    None => self.overflow(),
+                }
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<i64, AsmError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Here) => Ok(self.here as i64),
+            Some(Token::Name(name)) => self.eval.lookup(self.line, &name, self.here, self.stack),
+            Some(Token::Op('(')) => {
+                let value = self.sum()?;
+                match self.next() {
+                    Some(Token::Op(')')) => Ok(value),
+                    _ => error(self.line, "missing ')'".to_string()),
+                }
+            }
+            Some(token) => error(self.line, format!("unexpected {:?}", token)),
+            None => error(self.line, "expression ends too soon".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm;
+    use crate::Intcode;
+
+    #[test]
+    fn assemble_quine() {
+        let source = "
+            start:  rbo #1
+                    out rb-1
+                    add [counter], #1, [counter]
+                    eq [counter], #16, [flag]
+                    jf [flag], #start
+                    hlt
+            counter = 100
+            flag = counter + 1
+        ";
+
+        assert_eq!(assemble(source), Ok(vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99]));
+    }
+
+    #[test]
+    fn labels_data_and_expressions() {
+        let source = "
+                in [x]              ; read a value
+                mul [x], #(2 * 3 - 1), [x]
+                out [x]
+                jt #1, #end
+                db 1, 2, -3
+            x:  data 0
+            end: hlt
+        ";
+
+        let program = assemble(source).unwrap();
+        assert_eq!(program, vec![3,14,1002,14,5,14,4,14,1105,1,15,1,2,-3,0,99]);
+
+        let mut intcode = Intcode::new(program);
+        intcode.add_input(4);
+        intcode.process().unwrap();
+        assert_eq!(intcode.get_output(), Some(20));
+    }
+
+    #[test]
+    fn current_address() {
+        assert_eq!(assemble("jt #1, #$\nout #$"), Ok(vec![1105,1,0,104,3]));
+    }
+
+    #[test]
+    fn disassembly_round_trip() {
+        let program = vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+
+        let source: String = disasm::disassemble(&program)
+            .iter()
+            .map(|line| {
+                let text = line.to_string();
+                let text = &text[text.find(':').unwrap() + 1..];
+                format!("{}\n", text)
+            })
+            .collect();
+
+        assert_eq!(assemble(&source), Ok(program));
+    }
+
+    #[test]
+    fn errors_report_line() {
+        assert_eq!(assemble("hlt\nfoo #1").unwrap_err().line, 2);
+        assert_eq!(assemble("add #1, #2").unwrap_err().line, 1);
+        assert_eq!(assemble("add #1, #2, #3").unwrap_err().line, 1);
+        assert_eq!(assemble("out 5").unwrap_err().line, 1);
+        assert_eq!(assemble("\n\nout [nowhere]").unwrap_err().line, 3);
+        assert_eq!(assemble("a = b\nb = a\nout #a").unwrap_err().line, 1);
+        assert_eq!(assemble("x: hlt\nx: hlt").unwrap_err().line, 2);
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt::Write;
+
+use crate::disasm::{self, Item};
+use crate::Instruction;
+
+/*
+    Control-flow graph of a program image, built from the same code
+    discovery as disasm: execution is followed from address 0 through
+    fall-throughs and jump targets given in immediate mode.
+
+    A block ends at a jump or halt, before a jump target, or where the
+    code runs into data. Jumps whose target is read from memory get an
+    Unknown edge, since where they go depends on the run, and so do
+    paths into something that isn't the start of a block, like the
+    middle of an instruction or past the end of the image.
+*/
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Edge {
+    FallThrough(usize),
+    Jump(usize),
+    Unknown,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Block {
+    pub start: usize,
+    // address just past the last instruction
+    pub end: usize,
+    pub instructions: Vec<(usize, Instruction, Vec<i64>)>,
+    pub successors: Vec<Edge>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+}
+
+fn is_jump(instr: &Instruction) -> bool {
+    matches!(instr, Instruction::JumpIfTrue(..) | Instruction::JumpIfFalse(..))
+}
+
+/*
+    Whether the jump at the end of a block may go somewhere
+    that can only be known at run time.
+*/
+fn has_unknown_target(instr: &Instruction, params: &[i64]) -> bool {
+    let (cond, target) = match *instr {
+        Instruction::JumpIfTrue(c, t) | Instruction::JumpIfFalse(c, t) => (c, t),
+        _ => return false,
+    };
+
+    let never = cond == '1'
+        && match instr {
+            Instruction::JumpIfTrue(..) => params[0] == 0,
+            _ => params[0] != 0,
+        };
+
+    target != '1' && !never
+}
+
+pub fn build(program: &[i64]) -> Cfg {
+    let lines = disasm::disassemble(program);
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut current: Option<Block> = None;
+
+    for line in lines {
+        let (instr, params) = match line.item {
+            Item::Code(instr, params) => (instr, params),
+            Item::Data(_) => {
+                blocks.extend(current.take());
+                continue;
+            }
+        };
+
+        if !line.jumps_from.is_empty() {
+            blocks.extend(current.take());
+        }
+
+        let block = current.get_or_insert_with(|| Block {
+            start: line.address,
+            end: line.address,
+            instructions: Vec::new(),
+            successors: Vec::new(),
+        });
+        block.end = line.address + instr.size();
+        block.instructions.push((line.address, instr, params));
+
+        if is_jump(&instr) || instr == Instruction::Halt {
+            blocks.extend(current.take());
+        }
+    }
+    blocks.extend(current.take());
+
+    let starts: Vec<usize> = blocks.iter().map(|b| b.start).collect();
+
+    for block in blocks.iter_mut() {
+        let (address, instr, params) = block.instructions.last().unwrap();
+
+        let mut unknown = has_unknown_target(instr, params);
+
+        for target in disasm::successors(*address, instr, params) {
+            if !starts.contains(&target) {
+                unknown = true;
+                continue;
+            }
+            block.successors.push(if target == block.end { Edge::FallThrough(target) } else { Edge::Jump(target) });
+        }
+
+        if unknown {
+            block.successors.push(Edge::Unknown);
+        }
+    }
+
+    Cfg { blocks }
+}
+
+impl Cfg {
+    pub fn block_at(&self, address: usize) -> Option<&Block> {
+        self.blocks.iter().find(|b| b.start <= address && address < b.end)
+    }
+
+    /*
+        Graphviz source: one box per block listing its instructions.
+        Jumps are solid, fall-throughs dashed, and unknown jumps lead
+        to a single '?' node.
+    */
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let unknown = self.blocks.iter().any(|b| b.successors.contains(&Edge::Unknown));
+
+        out.push_str("digraph intcode {\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in &self.blocks {
+            let mut label = String::new();
+            for (address, instr, params) in &block.instructions {
+                let _ = write!(label, "{}: {}\\l", address, disasm::format_instruction(instr, params));
+            }
+            let _ = writeln!(out, "    b{} [label=\"{}\"];", block.start, label);
+        }
+
+        if unknown {
+            out.push_str("    unknown [label=\"?\", shape=circle];\n");
+        }
+
+        for block in &self.blocks {
+            for edge in &block.successors {
+                let _ = match edge {
+                    Edge::FallThrough(to) => writeln!(out, "    b{} -> b{} [style=dashed];", block.start, to),
+                    Edge::Jump(to) => writeln!(out, "    b{} -> b{};", block.start, to),
+                    Edge::Unknown => writeln!(out, "    b{} -> unknown [style=dotted];", block.start),
+                };
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_line_is_one_block() {
+        let cfg = build(&[1,9,10,3,2,3,11,0,99,30,40,50]);
+
+        assert_eq!(cfg.blocks.len(), 1);
+        assert_eq!(cfg.blocks[0].start, 0);
+        assert_eq!(cfg.blocks[0].end, 9);
+        assert_eq!(cfg.blocks[0].successors, vec![]);
+    }
+
+    #[test]
+    fn loop_and_exit() {
+        let cfg = build(&[109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99]);
+
+        let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(starts, vec![0, 15]);
+        assert_eq!(cfg.blocks[0].successors, vec![Edge::Jump(0), Edge::FallThrough(15)]);
+        assert_eq!(cfg.block_at(8).map(|b| b.start), Some(0));
+    }
+
+    #[test]
+    fn jump_target_splits_block() {
+        // 0: in [10]  2: jf [10], #7  5: out [10]  7: out #1  9: hlt
+        let cfg = build(&[3,10,1006,10,7,4,10,104,1,99,0]);
+
+        let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(starts, vec![0, 5, 7]);
+        assert_eq!(cfg.blocks[1].successors, vec![Edge::FallThrough(7)]);
+        assert_eq!(cfg.blocks[2].successors, vec![]);
+    }
+
+    #[test]
+    fn indirect_jump_is_unknown() {
+        let cfg = build(&[3,8,6,8,9,99,0,0,0,0]);
+
+        assert_eq!(cfg.blocks[0].successors, vec![Edge::FallThrough(5), Edge::Unknown]);
+        assert!(cfg.to_dot().contains("    b0 -> unknown [style=dotted];\n"));
+    }
+
+    #[test]
+    fn jump_outside_blocks_is_unknown() {
+        // past the end of the image
+        let cfg = build(&[1105,1,50,99]);
+        assert_eq!(cfg.blocks[0].successors, vec![Edge::Unknown]);
+
+        // into the middle of the add at 3
+        let cfg = build(&[1005,9,4,1101,1,1,9,99,0,0]);
+        assert_eq!(cfg.blocks[0].successors, vec![Edge::FallThrough(3), Edge::Unknown]);
+    }
+
+    #[test]
+    fn dot_output() {
+        let cfg = build(&[1105,1,4,7,99]);
+
+        assert_eq!(
+            cfg.to_dot(),
+            "digraph intcode {\n\
+             \x20   node [shape=box, fontname=\"monospace\"];\n\
+             \x20   b0 [label=\"0: jt #1, #4\\l\"];\n\
+             \x20   b4 [label=\"4: hlt\\l\"];\n\
+             \x20   b0 -> b4;\n\
+             }\n"
+        );
+    }
+}
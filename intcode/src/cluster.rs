@@ -0,0 +1,439 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::{Input, Intcode, IntcodeError, Output, State};
+
+/*
+    A network of machines, each on its own thread, talking in packets.
+
+    Every machine is first given its address as input. After that an
+    input instruction gets the X then Y of the next packet sent to it,
+    or -1 when nothing is waiting. Machines send a packet by outputting
+    three values: destination address, X and Y.
+
+    Packets for an address outside 0..size go to the Monitor, which
+    is also told whenever the network goes idle.
+*/
+
+// empty reads in a row, the current one included, before a machine
+// waiting in input counts as blocked
+const IDLE_POLLS: usize = 2;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Action {
+    Continue,
+    Send(Packet),
+    Stop,
+}
+
+pub trait Monitor {
+    /*
+        Called with every packet sent to an address outside the network.
+    */
+    fn on_packet(&mut self, packet: Packet) -> Action;
+
+    /*
+        Called once each time every machine is blocked on input
+        and no packets are in flight.
+    */
+    fn on_idle(&mut self) -> Action {
+        Action::Continue
+    }
+}
+
+/*
+    Keeps the last packet sent to its address and hands it to
+    machine 0 whenever the network is idle. Stops once it would send
+    the same Y twice in a row.
+*/
+pub struct Nat {
+    address: i64,
+    last: Option<Packet>,
+    last_sent_y: Option<i64>,
+    pub first: Option<Packet>,
+    pub repeated_y: Option<i64>,
+}
+
+impl Nat {
+    pub fn new(address: i64) -> Nat {
+        Nat { address, last: None, last_sent_y: None, first: None, repeated_y: None }
+    }
+}
+
+impl Monitor for Nat {
+    fn on_packet(&mut self, packet: Packet) -> Action {
+        if packet.dest == self.address {
+            if self.first.is_none() {
+                self.first = Some(packet);
+            }
+            self.last = Some(packet);
+        }
+
+        Action::Continue
+    }
+
+    fn on_idle(&mut self) -> Action {
+        let packet = match self.last {
+            Some(packet) => packet,
+            None => return Action::Continue,
+        };
+
+        if self.last_sent_y == Some(packet.y) {
+            self.repeated_y = Some(packet.y);
+            return Action::Stop;
+        }
+
+        self.last_sent_y = Some(packet.y);
+        Action::Send(Packet { dest: 0, ..packet })
+    }
+}
+
+enum Event {
+    Packet(Packet),
+    Halted,
+    Failed(IntcodeError),
+}
+
+struct Shared {
+    in_flight: AtomicUsize,
+    activity: AtomicUsize,
+    empty_polls: Vec<AtomicUsize>,
+    // set only while a machine is inside input() after empty polls, or once it has halted
+    blocked: Vec<AtomicBool>,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn new(size: usize) -> Shared {
+        Shared {
+            in_flight: AtomicUsize::new(0),
+            activity: AtomicUsize::new(0),
+            empty_polls: (0..size).map(|_| AtomicUsize::new(0)).collect(),
+            blocked: (0..size).map(|_| AtomicBool::new(false)).collect(),
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && self.blocked.iter().all(|b| b.load(Ordering::SeqCst))
+    }
+
+    fn deliver(&self, senders: &[Sender<Packet>], packet: Packet) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        self.activity.fetch_add(1, Ordering::SeqCst);
+
+        if senders[packet.dest as usize].send(packet).is_err() {
+            // the machine is gone, so the packet will never land
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+struct NodeInput {
+    address: usize,
+    packets: Receiver<Packet>,
+    pending: VecDeque<i64>,
+    shared: Arc<Shared>,
+}
+
+impl Input for NodeInput {
+    fn input(&mut self) -> Option<i64> {
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        if let Some(value) = self.pending.pop_front() {
+            return Some(value);
+        }
+
+        let empty_polls = &self.shared.empty_polls[self.address];
+        let blocked = &self.shared.blocked[self.address];
+        if empty_polls.load(Ordering::SeqCst) + 1 >= IDLE_POLLS {
+            blocked.store(true, Ordering::SeqCst);
+        }
+
+        let received = self.packets.recv_timeout(POLL_INTERVAL);
+        // busy again before a packet stops counting as in flight
+        blocked.store(false, Ordering::SeqCst);
+
+        match received {
+            Ok(packet) => {
+                empty_polls.store(0, Ordering::SeqCst);
+                self.shared.in_flight.fetch_sub(1, Ordering::SeqCst);
+                self.pending.push_back(packet.y);
+                Some(packet.x)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                empty_polls.fetch_add(1, Ordering::SeqCst);
+                Some(-1)
+            }
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+struct NodeOutput {
+    address: usize,
+    buffer: Vec<i64>,
+    senders: Vec<Sender<Packet>>,
+    events: Sender<Event>,
+    shared: Arc<Shared>,
+}
+
+impl Output for NodeOutput {
+    fn output(&mut self, value: i64) {
+        self.shared.empty_polls[self.address].store(0, Ordering::SeqCst);
+        self.buffer.push(value);
+
+        if self.buffer.len() < 3 {
+            return;
+        }
+
+        let packet = Packet { dest: self.buffer[0], x: self.buffer[1], y: self.buffer[2] };
+        self.buffer.clear();
+
+        if packet.dest >= 0 && (packet.dest as usize) < self.senders.len() {
+            self.shared.deliver(&self.senders, packet);
+        } else {
+            let _ = self.events.send(Event::Packet(packet));
+        }
+    }
+}
+
+/*
+    Like Intcode::process, but gives up as soon as the cluster shuts
+    down, even in a machine that never reads input again.
+*/
+fn run_node(intcode: &mut Intcode, shared: &Shared) -> Result<State, IntcodeError> {
+    loop {
+        if shared.shutdown.load(Ordering::Relaxed) {
+            return Ok(State::Processing);
+        }
+
+        let step = intcode.step()?;
+        if step.state != State::Processing {
+            return Ok(step.state);
+        }
+    }
+}
+
+/*
+    Runs size copies of program until the monitor returns Action::Stop,
+    every machine halts, or one of them fails. Every machine thread has
+    stopped by the time it returns.
+*/
+pub fn run_cluster<M: Monitor>(program: &[i64], size: usize, monitor: &mut M) -> Result<(), IntcodeError> {
+    let shared = Arc::new(Shared::new(size));
+
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..size).map(|_| mpsc::channel()).unzip();
+    let (events_tx, events) = mpsc::channel();
+
+    let mut handles = Vec::new();
+
+    for (address, packets) in receivers.into_iter().enumerate() {
+        let mut intcode = Intcode::new(program.to_vec());
+        intcode.add_input(address as i64);
+        intcode.set_input_device(NodeInput {
+            address,
+            packets,
+            pending: VecDeque::new(),
+            shared: shared.clone(),
+        });
+        intcode.set_output_device(NodeOutput {
+            address,
+            buffer: Vec::new(),
+            senders: senders.clone(),
+            events: events_tx.clone(),
+            shared: shared.clone(),
+        });
+
+        let events = events_tx.clone();
+        let shared = shared.clone();
+
+        handles.push(thread::spawn(move || {
+            match run_node(&mut intcode, &shared) {
+                Ok(State::Halted) => {
+                    // a halted machine will never read again
+                    shared.blocked[address].store(true, Ordering::SeqCst);
+                    let _ = events.send(Event::Halted);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = events.send(Event::Failed(e));
+                }
+            }
+        }));
+    }
+
+    drop(events_tx);
+
+    let result = watch(&shared, &senders, &events, size, monitor);
+
+    shared.shutdown.store(true, Ordering::SeqCst);
+    drop(senders);
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    result
+}
+
+fn watch<M: Monitor>(shared: &Shared, senders: &[Sender<Packet>], events: &Receiver<Event>, size: usize, monitor: &mut M) -> Result<(), IntcodeError> {
+    let mut halted = 0;
+    let mut idle_reported_at = None;
+
+    loop {
+        let action = match events.recv_timeout(POLL_INTERVAL * 2) {
+            Ok(Event::Packet(packet)) => monitor.on_packet(packet),
+            Ok(Event::Halted) => {
+                halted += 1;
+                if halted == size {
+                    return Ok(());
+                }
+                Action::Continue
+            }
+            Ok(Event::Failed(e)) => return Err(e),
+            Err(RecvTimeoutError::Timeout) => {
+                let activity = shared.activity.load(Ordering::SeqCst);
+                if shared.is_idle() && idle_reported_at != Some(activity) {
+                    idle_reported_at = Some(activity);
+                    monitor.on_idle()
+                } else {
+                    Action::Continue
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+
+        match action {
+            Action::Continue => {}
+            Action::Send(packet) => {
+                if packet.dest >= 0 && (packet.dest as usize) < size {
+                    shared.deliver(senders, packet);
+                }
+            }
+            Action::Stop => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+        Machine 0 starts by sending (1, 10, 20). Every machine forwards
+        each packet it gets to its address + 1 with X bumped by one.
+    */
+    fn relay() -> Vec<i64> {
+        vec![
+            3,100,
+            1008,100,0,101,
+            1006,101,15,
+            104,1,104,10,104,20,
+            3,102,
+            1008,102,-1,103,
+            1005,103,15,
+            3,104,
+            1001,100,1,105,
+            4,105,
+            1001,102,1,102,
+            4,102,
+            4,104,
+            1105,1,15,
+        ]
+    }
+
+    struct FirstPacket(Option<Packet>);
+
+    impl Monitor for FirstPacket {
+        fn on_packet(&mut self, packet: Packet) -> Action {
+            self.0 = Some(packet);
+            Action::Stop
+        }
+    }
+
+    #[test]
+    fn packets_leave_the_network() {
+        let mut monitor = FirstPacket(None);
+
+        run_cluster(&relay(), 4, &mut monitor).unwrap();
+
+        assert_eq!(monitor.0, Some(Packet { dest: 4, x: 13, y: 20 }));
+    }
+
+    #[test]
+    fn nat_restarts_idle_network() {
+        let mut nat = Nat::new(4);
+
+        run_cluster(&relay(), 4, &mut nat).unwrap();
+
+        assert_eq!(nat.first, Some(Packet { dest: 4, x: 13, y: 20 }));
+        assert_eq!(nat.last, Some(Packet { dest: 4, x: 17, y: 20 }));
+        assert_eq!(nat.repeated_y, Some(20));
+    }
+
+    #[test]
+    fn fifty_machines() {
+        let mut monitor = FirstPacket(None);
+
+        run_cluster(&relay(), 50, &mut monitor).unwrap();
+
+        assert_eq!(monitor.0, Some(Packet { dest: 50, x: 59, y: 20 }));
+    }
+
+    #[test]
+    fn all_halted_ends_run() {
+        let mut monitor = FirstPacket(None);
+
+        run_cluster(&[3,0,99], 3, &mut monitor).unwrap();
+
+        assert_eq!(monitor.0, None);
+    }
+
+    #[test]
+    fn busy_machine_is_not_idle() {
+        let shared = Arc::new(Shared::new(1));
+        let (_sender, packets) = mpsc::channel();
+        let mut input = NodeInput { address: 0, packets, pending: VecDeque::new(), shared: shared.clone() };
+
+        // two empty polls, then the machine goes off to work without output
+        assert_eq!(input.input(), Some(-1));
+        assert_eq!(input.input(), Some(-1));
+        assert!(!shared.is_idle());
+    }
+
+    #[test]
+    fn stop_ends_machines_that_never_read() {
+        // sends one packet out, then spins forever
+        let program = vec![104,255,104,1,104,2,1105,1,6];
+        let mut monitor = FirstPacket(None);
+
+        run_cluster(&program, 2, &mut monitor).unwrap();
+
+        assert_eq!(monitor.0, Some(Packet { dest: 255, x: 1, y: 2 }));
+    }
+
+    #[test]
+    fn failure_is_reported() {
+        let mut monitor = FirstPacket(None);
+
+        let result = run_cluster(&[3,0,42], 2, &mut monitor);
+
+        assert_eq!(result, Err(IntcodeError::UnknownOpcode { ip: 2, instruction: 42 }));
+    }
+}
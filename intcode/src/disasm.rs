@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{process_instruction, Instruction};
+
+/*
+    Turns a program image into an annotated listing.
+
+    Code is found by following execution from address 0: fall-throughs,
+    and jump targets given in immediate mode. Words never reached that
+    way, or that don't decode as an instruction, are listed as data.
+*/
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Item {
+    Code(Instruction, Vec<i64>),
+    Data(i64),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Line {
+    pub address: usize,
+    pub item: Item,
+    // addresses of the jumps that land here
+    pub jumps_from: Vec<usize>,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>6}: ", self.address)?;
+
+        match &self.item {
+            Item::Code(instr, params) => write!(f, "{}", format_instruction(instr, params))?,
+            Item::Data(value) => write!(f, "data {}", value)?,
+        }
+
+        if !self.jumps_from.is_empty() {
+            let from: Vec<String> = self.jumps_from.iter().map(|a| a.to_string()).collect();
+            write!(f, "    ; from {}", from.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn format_operand(mode: char, value: i64) -> String {
+    match mode {
+        '1' => format!("#{}", value),
+        '2' if value < 0 => format!("rb{}", value),
+        '2' => format!("rb+{}", value),
+        _ => format!("[{}]", value),
+    }
+}
+
+pub fn format_instruction(instr: &Instruction, params: &[i64]) -> String {
+    let operands: Vec<String> = instr
+        .modes()
+        .iter()
+        .zip(params)
+        .map(|(mode, value)| format_operand(*mode, *value))
+        .collect();
+
+    if operands.is_empty() {
+        instr.mnemonic().to_string()
+    } else {
+        format!("{} {}", instr.mnemonic(), operands.join(", "))
+    }
+}
+
+/*
+    Decodes the instruction at address if it is one the machine would
+    accept: a known opcode, known modes, and no immediate-mode writes.
+*/
+pub fn decode_at(program: &[i64], address: usize) -> Option<(Instruction, Vec<i64>)> {
+    let instr = process_instruction(*program.get(address)?)?;
+    let modes = instr.modes();
+
+    if modes.iter().any(|m| !matches!(m, '0' | '1' | '2')) {
+        return None;
+    }
+
+    if instr.writes() && modes.last() == Some(&'1') {
+        return None;
+    }
+
+    let params = (1..=modes.len())
+        .map(|i| program.get(address + i).copied().unwrap_or(0))
+        .collect();
+
+    Some((instr, params))
+}
+
+/*
+    Where control can go after the instruction at address,
+    leaving out jumps whose target isn't known statically.
+*/
+pub(crate) fn successors(address: usize, instr: &Instruction, params: &[i64]) -> Vec<usize> {
+    let next = address + instr.size();
+
+    match *instr {
+        Instruction::Halt => vec![],
+        Instruction::JumpIfTrue(c, t) | Instruction::JumpIfFalse(c, t) => {
+            let mut targets = Vec::new();
+
+            let taken = |cond: i64| match instr {
+                Instruction::JumpIfTrue(..) => cond != 0,
+                _ => cond == 0,
+            };
+            let always = c == '1' && taken(params[0]);
+            let never = c == '1' && !taken(params[0]);
+
+            if t == '1' && params[1] >= 0 && !never {
+                targets.push(params[1] as usize);
+            }
+            if !always {
+                targets.push(next);
+            }
+            targets
+        }
+        _ => vec![next],
+    }
+}
+
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut code: BTreeMap<usize, (Instruction, Vec<i64>)> = BTreeMap::new();
+    let mut jumps_from: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut to_visit = vec![0];
+
+    while let Some(address) = to_visit.pop() {
+        if address >= program.len() || code.contains_key(&address) {
+            continue;
+        }
+
+        if let Some((instr, params)) = decode_at(program, address) {
+            for target in successors(address, &instr, &params) {
+                if target != address + instr.size() {
+                    jumps_from.entry(target).or_default().push(address);
+                }
+                to_visit.push(target);
+            }
+            code.insert(address, (instr, params));
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut address = 0;
+
+    while address < program.len() {
+        let from = jumps_from.remove(&address).unwrap_or_default();
+
+        match code.get(&address) {
+            Some((instr, params)) => {
+                lines.push(Line { address, item: Item::Code(*instr, params.clone()), jumps_from: from });
+                address += instr.size();
+            }
+            None => {
+                lines.push(Line { address, item: Item::Data(program[address]), jumps_from: from });
+                address += 1;
+            }
+        }
+    }
+
+    lines
+}
+
+pub fn listing(program: &[i64]) -> String {
+    let mut out = String::new();
+
+    for line in disassemble(program) {
+        out.push_str(&line.to_string());
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operand_syntax() {
+        assert_eq!(format_operand('0', 12), "[12]");
+        assert_eq!(format_operand('1', 5), "#5");
+        assert_eq!(format_operand('2', 3), "rb+3");
+        assert_eq!(format_operand('2', -1), "rb-1");
+    }
+
+    #[test]
+    fn simple_listing() {
+        let program = vec![1002,4,3,4,33];
+
+        assert_eq!(listing(&program), "     0: mul [4], #3, [4]\n     4: data 33\n");
+    }
+
+    #[test]
+    fn quine_listing() {
+        let program = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let lines: Vec<String> = disassemble(&program).iter().map(|l| l.to_string()).collect();
+
+        assert_eq!(lines, vec![
+            "     0: rbo #1    ; from 12",
+            "     2: out rb-1",
+            "     4: add [100], #1, [100]",
+            "     8: eq [100], #16, [101]",
+            "    12: jf [101], #0",
+            "    15: hlt",
+        ]);
+    }
+
+    #[test]
+    fn data_after_unconditional_jump() {
+        let program = vec![1105,1,4,7,99];
+        let lines = disassemble(&program);
+
+        assert_eq!(lines[1].item, Item::Data(7));
+        assert_eq!(lines[2].item, Item::Code(Instruction::Halt, vec![]));
+        assert_eq!(lines[2].jumps_from, vec![0]);
+
+        // any non-zero condition jumps
+        let lines = disassemble(&[1105,-1,4,7,99]);
+        assert_eq!(lines[1].item, Item::Data(7));
+    }
+
+    #[test]
+    fn undecodable_words_are_data() {
+        let program = vec![1,0,0,0,42,99];
+        let lines = disassemble(&program);
+
+        assert_eq!(lines[1].item, Item::Data(42));
+        assert_eq!(lines[2].item, Item::Data(99));
+    }
+}
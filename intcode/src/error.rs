@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::fmt;
+
+/*
+    Every variant carries the address of the instruction that failed (ip)
+    and the raw value found there, so a bad program can be tracked down
+    without re-running it.
+*/
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum IntcodeError {
+    UnknownOpcode { ip: usize, instruction: i64 },
+    InvalidMode { ip: usize, instruction: i64, mode: char },
+    AddressOutOfBounds { ip: usize, instruction: i64, address: usize },
+    NegativeAddress { ip: usize, instruction: i64, address: i64 },
+    MissingInput { ip: usize, instruction: i64 },
+    EndOfMemory { ip: usize, instruction: i64 },
+    // an add, multiply or relative base result outside the range of i64
+    Overflow { ip: usize, instruction: i64 },
+}
+
+impl IntcodeError {
+    pub fn ip(&self) -> usize {
+        match *self {
+            IntcodeError::UnknownOpcode { ip, .. } => ip,
+            IntcodeError::InvalidMode { ip, .. } => ip,
+            IntcodeError::AddressOutOfBounds { ip, .. } => ip,
+            IntcodeError::NegativeAddress { ip, .. } => ip,
+            IntcodeError::MissingInput { ip, .. } => ip,
+            IntcodeError::EndOfMemory { ip, .. } => ip,
+            IntcodeError::Overflow { ip, .. } => ip,
+        }
+    }
+
+    pub fn instruction(&self) -> i64 {
+        match *self {
+            IntcodeError::UnknownOpcode { instruction, .. } => instruction,
+            IntcodeError::InvalidMode { instruction, .. } => instruction,
+            IntcodeError::AddressOutOfBounds { instruction, .. } => instruction,
+            IntcodeError::NegativeAddress { instruction, .. } => instruction,
+            IntcodeError::MissingInput { instruction, .. } => instruction,
+            IntcodeError::EndOfMemory { instruction, .. } => instruction,
+            IntcodeError::Overflow { instruction, .. } => instruction,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { ip, instruction } => {
+                write!(f, "unknown opcode in {} at {}", instruction, ip)
+            }
+            IntcodeError::InvalidMode { ip, instruction, mode } => {
+                write!(f, "invalid mode '{}' in {} at {}", mode, instruction, ip)
+            }
+            IntcodeError::AddressOutOfBounds { ip, instruction, address } => {
+                write!(f, "address {} out of bounds for {} at {}", address, instruction, ip)
+            }
+            IntcodeError::NegativeAddress { ip, instruction, address } => {
+                write!(f, "negative address {} for {} at {}", address, instruction, ip)
+            }
+            IntcodeError::MissingInput { ip, instruction } => {
+                write!(f, "no input for {} at {}", instruction, ip)
+            }
+            IntcodeError::EndOfMemory { ip, instruction } => {
+                write!(f, "ran off the end of memory after {} at {}", instruction, ip)
+            }
+            IntcodeError::Overflow { ip, instruction } => {
+                write!(f, "arithmetic overflow in {} at {}", instruction, ip)
+            }
+        }
+    }
+}
+
+impl Error for IntcodeError {}
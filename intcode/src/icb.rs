@@ -0,0 +1,186 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::Intcode;
+
+/*
+    The .icb binary program format, a compact alternative to the
+    comma-separated text:
+
+        header:  "ICB" then a version byte
+                 word count, entry point
+        body:    the words
+
+    Counts and the entry point are unsigned LEB128 varints. Words are
+    zigzag encoded first so small negative numbers stay short too.
+*/
+
+const MAGIC: &[u8; 3] = b"ICB";
+const VERSION: u8 = 1;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Image {
+    pub words: Vec<i64>,
+    // address execution starts at
+    pub entry: usize,
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn write_varint(w: &mut dyn Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(r: &mut dyn Read) -> io::Result<u64> {
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0; 1];
+        r.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid("varint too long"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl Image {
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_varint(&mut w, self.words.len() as u64)?;
+        write_varint(&mut w, self.entry as u64)?;
+        for &word in &self.words {
+            write_varint(&mut w, zigzag(word))?;
+        }
+
+        w.flush()
+    }
+
+    pub fn read<R: Read>(mut r: R) -> io::Result<Image> {
+        let mut header = [0; 4];
+        r.read_exact(&mut header)?;
+        if &header[..3] != MAGIC {
+            return Err(invalid("not an icb program"));
+        }
+        if header[3] != VERSION {
+            return Err(invalid("unsupported icb version"));
+        }
+
+        let count = read_varint(&mut r)? as usize;
+        let entry = read_varint(&mut r)? as usize;
+
+        // the count is only a hint, the data has to back it up
+        let mut words = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            words.push(unzigzag(read_varint(&mut r)?));
+        }
+
+        Ok(Image { words, entry })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let _ = self.write(&mut bytes);
+        bytes
+    }
+}
+
+impl Intcode {
+    /*
+        A machine loaded from an .icb image, ready to start at its entry point.
+    */
+    pub fn from_icb<R: Read>(reader: R) -> io::Result<Intcode> {
+        let image = Image::read(reader)?;
+        let mut intcode = Intcode::new(image.words);
+        intcode.ip = image.entry;
+
+        Ok(intcode)
+    }
+
+    pub fn load_icb<P: AsRef<Path>>(path: P) -> io::Result<Intcode> {
+        Intcode::from_icb(BufReader::new(File::open(path)?))
+    }
+
+    /*
+        Memory as it is now, with the current ip as the entry point.
+    */
+    pub fn to_icb(&self) -> Image {
+        Image { words: self.memory(), entry: self.ip }
+    }
+
+    pub fn save_icb<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.to_icb().write(BufWriter::new(File::create(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zigzag_round_trip() {
+        for value in [0, 1, -1, 63, -64, 1125899906842624, i64::MAX, i64::MIN] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn layout() {
+        let image = Image { words: vec![1, -1, 300], entry: 2 };
+
+        assert_eq!(image.to_bytes(), vec![b'I', b'C', b'B', 1, 3, 2, 2, 1, 0xd8, 0x04]);
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let image = Image { words: vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99, i64::MIN], entry: 0 };
+        let bytes = image.to_bytes();
+
+        assert_eq!(Image::read(&bytes[..]).unwrap(), image);
+    }
+
+    #[test]
+    fn resumes_at_entry_point() {
+        let mut intcode = Intcode::new(vec![104,-7,3,9,4,9,99,0,0,0]);
+        intcode.process().unwrap();
+
+        let bytes = intcode.to_icb().to_bytes();
+        let mut loaded = Intcode::from_icb(&bytes[..]).unwrap();
+        assert_eq!(loaded.get_ip(), 2);
+
+        loaded.add_input(12);
+        loaded.process().unwrap();
+        assert_eq!(loaded.take_outputs(), vec![12]);
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        assert!(Image::read(&b"ICT\x01\x00\x00"[..]).is_err());
+        assert!(Image::read(&b"ICB\x02\x00\x00"[..]).is_err());
+        // says three words, holds one
+        assert!(Image::read(&b"ICB\x01\x03\x00\x02"[..]).is_err());
+    }
+}
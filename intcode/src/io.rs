@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/*
+    A source of values for the Input instruction.
+
+    Returning None means no value is available right now; the machine
+    stops with State::InputRequired and retries the same instruction the
+    next time it is processed.
+*/
+pub trait Input {
+    fn input(&mut self) -> Option<i64>;
+}
+
+/*
+    A sink for values produced by the Output instruction.
+*/
+pub trait Output {
+    fn output(&mut self, value: i64);
+}
+
+impl Input for VecDeque<i64> {
+    fn input(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl Output for VecDeque<i64> {
+    fn output(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+/*
+    A queue the host keeps a handle to after attaching it to a machine,
+    so it can keep feeding inputs or collecting outputs.
+*/
+pub type SharedQueue = Arc<Mutex<VecDeque<i64>>>;
+
+impl Input for SharedQueue {
+    fn input(&mut self) -> Option<i64> {
+        self.lock().unwrap().pop_front()
+    }
+}
+
+impl Output for SharedQueue {
+    fn output(&mut self, value: i64) {
+        self.lock().unwrap().push_back(value);
+    }
+}
+
+pub struct IterInput<I>(pub I);
+
+impl<I> Input for IterInput<I>
+where
+    I: Iterator<Item = i64>,
+{
+    fn input(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+pub struct FnInput<F>(pub F);
+
+impl<F> Input for FnInput<F>
+where
+    F: FnMut() -> Option<i64>,
+{
+    fn input(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+pub struct FnOutput<F>(pub F);
+
+impl<F> Output for FnOutput<F>
+where
+    F: FnMut(i64),
+{
+    fn output(&mut self, value: i64) {
+        (self.0)(value)
+    }
+}
+
+/*
+    Blocks until a value arrives. A disconnected channel reads as
+    no input available.
+*/
+impl Input for Receiver<i64> {
+    fn input(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+/*
+    Values sent after the receiving end is dropped are discarded.
+*/
+impl Output for Sender<i64> {
+    fn output(&mut self, value: i64) {
+        let _ = self.send(value);
+    }
+}
+
+/*
+    Reads integers as text from stdin. A line may hold several values
+    separated by whitespace or commas. A token that doesn't parse ends
+    the input, like end of file: values before it on its line are still
+    read, then the machine stops with State::InputRequired for good.
+*/
+pub struct StdinInput {
+    pending: VecDeque<i64>,
+    ended: bool,
+}
+
+impl StdinInput {
+    pub fn new() -> StdinInput {
+        StdinInput { pending: VecDeque::new(), ended: false }
+    }
+
+    fn read_from<R: BufRead>(&mut self, mut reader: R) -> Option<i64> {
+        while self.pending.is_empty() && !self.ended {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => {}
+            }
+
+            for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+                if token.is_empty() {
+                    continue;
+                }
+                match token.parse::<i64>() {
+                    Ok(value) => self.pending.push_back(value),
+                    Err(_) => {
+                        self.ended = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+impl Default for StdinInput {
+    fn default() -> Self {
+        StdinInput::new()
+    }
+}
+
+impl Input for StdinInput {
+    fn input(&mut self) -> Option<i64> {
+        self.read_from(io::stdin().lock())
+    }
+}
+
+/*
+    Writes each value to stdout on its own line.
+*/
+pub struct StdoutOutput;
+
+impl Output for StdoutOutput {
+    fn output(&mut self, value: i64) {
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let _ = writeln!(handle, "{}", value);
+        let _ = handle.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::adder;
+    use crate::{Intcode, State};
+    use std::sync::mpsc;
+
+    #[test]
+    fn iterator_input() {
+        let mut intcode = Intcode::new(adder());
+        intcode.set_input_device(IterInput(vec![4, 5].into_iter()));
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.take_outputs(), vec![9]);
+    }
+
+    #[test]
+    fn closure_input_and_output() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+
+        let mut next = 0;
+        let mut intcode = Intcode::new(adder());
+        intcode.set_input_device(FnInput(move || {
+            next += 10;
+            Some(next)
+        }));
+        intcode.set_output_device(FnOutput(move |v| sink.lock().unwrap().push(v)));
+        intcode.process().unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), vec![30]);
+        assert_eq!(intcode.take_outputs(), Vec::<i64>::new());
+        assert_eq!(intcode.last_output(), Some(30));
+    }
+
+    #[test]
+    fn shared_queue_keeps_host_handle() {
+        let input = SharedQueue::default();
+        let output = SharedQueue::default();
+
+        let mut intcode = Intcode::new(adder());
+        intcode.set_input_device(input.clone());
+        intcode.set_output_device(output.clone());
+
+        input.lock().unwrap().push_back(1);
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+
+        input.lock().unwrap().push_back(2);
+        assert_eq!(intcode.process(), Ok(State::Halted));
+        assert_eq!(output.lock().unwrap().pop_front(), Some(3));
+    }
+
+    #[test]
+    fn channel_input_and_output() {
+        let (in_tx, in_rx) = mpsc::channel();
+        let (out_tx, out_rx) = mpsc::channel();
+
+        let mut intcode = Intcode::new(adder());
+        intcode.set_input_device(in_rx);
+        intcode.set_output_device(out_tx);
+
+        in_tx.send(20).unwrap();
+        in_tx.send(22).unwrap();
+        intcode.process().unwrap();
+
+        assert_eq!(out_rx.recv(), Ok(42));
+    }
+
+    #[test]
+    fn bad_token_ends_text_input() {
+        let mut text = &b"1, 2\n3 x 4\n5\n"[..];
+        let mut input = StdinInput::new();
+
+        let values: Vec<_> = std::iter::from_fn(|| input.read_from(&mut text)).collect();
+        assert_eq!(values, vec![1, 2, 3]);
+        assert_eq!(input.read_from(&mut text), None);
+    }
+
+    #[test]
+    fn queued_inputs_come_before_device() {
+        let mut intcode = Intcode::new(adder());
+        intcode.add_input(100);
+        intcode.set_input_device(IterInput(vec![1].into_iter()));
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_output(), Some(101));
+    }
+}
@@ -0,0 +1,588 @@
+use std::sync::Arc;
+
+use crate::{process_instruction, Instruction, Intcode, IntcodeError, State};
+
+/*
+    A faster engine for long-running programs.
+
+    Straight-line runs of code (basic blocks) are compiled once into
+    chains of closures with their operands already fetched and decoded,
+    then reused every time execution reaches the block again.
+
+    Compiled code only handles the common path. Anything unusual, like a
+    bad address, an unknown opcode or a halt, is handed to the interpreter
+    for that one instruction, so states and errors come out exactly as
+    Intcode::process would report them. When a program writes over code
+    that has been compiled, the blocks holding it are dropped and the
+    rewritten instruction is interpreted from then on.
+
+    Breakpoints, watchpoints, traces and profiles are only honoured by
+    the interpreter, so a machine using any of them just runs interpreted.
+*/
+
+const MAX_BLOCK: usize = 64;
+
+enum Flow {
+    Next,
+    Wrote(usize),
+    Jump(usize),
+    Wait,
+    Interpret,
+}
+
+type Op = Box<dyn Fn(&mut Intcode) -> Flow + Send + Sync>;
+
+struct Entry {
+    ip: usize,
+    next: usize,
+    op: Op,
+}
+
+struct Block {
+    start: usize,
+    end: usize,
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Position(usize),
+    Immediate(i64),
+    Relative(i64),
+}
+
+fn relative(intcode: &Intcode, offset: i64) -> Option<usize> {
+    let address = intcode.relative_base.checked_add(offset)?;
+    if address < 0 || address as usize >= intcode.memory_limit {
+        return None;
+    }
+
+    Some(address as usize)
+}
+
+fn load(intcode: &Intcode, operand: Operand) -> Option<i64> {
+    match operand {
+        Operand::Position(address) => Some(intcode.read(address)),
+        Operand::Immediate(value) => Some(value),
+        Operand::Relative(offset) => Some(intcode.read(relative(intcode, offset)?)),
+    }
+}
+
+fn target(intcode: &Intcode, operand: Operand) -> Option<usize> {
+    match operand {
+        Operand::Position(address) => Some(address),
+        Operand::Relative(offset) => relative(intcode, offset),
+        Operand::Immediate(_) => None,
+    }
+}
+
+fn binary<F>(x: Operand, y: Operand, z: Operand, f: F) -> Op
+where
+    F: Fn(i64, i64) -> Option<i64> + Send + Sync + 'static,
+{
+    Box::new(move |m: &mut Intcode| {
+        let (Some(a), Some(b), Some(dst)) = (load(m, x), load(m, y), target(m, z)) else {
+            return Flow::Interpret;
+        };
+        // overflow is left to the interpreter to report
+        let Some(value) = f(a, b) else {
+            return Flow::Interpret;
+        };
+        m.write(dst, value);
+        Flow::Wrote(dst)
+    })
+}
+
+fn jump<F>(x: Operand, y: Operand, taken: F) -> Op
+where
+    F: Fn(i64) -> bool + Send + Sync + 'static,
+{
+    Box::new(move |m: &mut Intcode| {
+        let (Some(cond), Some(to)) = (load(m, x), load(m, y)) else {
+            return Flow::Interpret;
+        };
+        if !taken(cond) {
+            return Flow::Next;
+        }
+        if to < 0 || to as usize >= m.instructions.len() {
+            return Flow::Interpret;
+        }
+        Flow::Jump(to as usize)
+    })
+}
+
+/*
+    The closure for the instruction at ip, or None if it should
+    always go through the interpreter.
+*/
+fn compile_op(intcode: &Intcode, ip: usize, instr: Instruction) -> Option<Op> {
+    let mut operands = Vec::new();
+
+    for (i, mode) in instr.modes().into_iter().enumerate() {
+        let param = intcode.read(ip + i + 1);
+        operands.push(match mode {
+            '0' if param >= 0 && (param as usize) < intcode.memory_limit => Operand::Position(param as usize),
+            '1' => Operand::Immediate(param),
+            '2' => Operand::Relative(param),
+            _ => return None,
+        });
+    }
+
+    if instr.writes() && matches!(operands.last(), Some(Operand::Immediate(_))) {
+        return None;
+    }
+
+    let op: Op = match (instr, &operands[..]) {
+        (Instruction::Add(..), &[x, y, z]) => binary(x, y, z, |a, b| a.checked_add(b)),
+        (Instruction::Multiply(..), &[x, y, z]) => binary(x, y, z, |a, b| a.checked_mul(b)),
+        (Instruction::LessThan(..), &[x, y, z]) => binary(x, y, z, |a, b| Some((a < b) as i64)),
+        (Instruction::Equals(..), &[x, y, z]) => binary(x, y, z, |a, b| Some((a == b) as i64)),
+        (Instruction::JumpIfTrue(..), &[x, y]) => jump(x, y, |cond| cond != 0),
+        (Instruction::JumpIfFalse(..), &[x, y]) => jump(x, y, |cond| cond == 0),
+        (Instruction::Input(_), &[x]) => Box::new(move |m: &mut Intcode| {
+            let Some(dst) = target(m, x) else {
+                return Flow::Interpret;
+            };
+            match m.get_input() {
+                Some(value) => {
+                    m.write(dst, value);
+                    Flow::Wrote(dst)
+                }
+                None => Flow::Wait,
+            }
+        }),
+        (Instruction::Output(_), &[x]) => Box::new(move |m: &mut Intcode| {
+            let Some(value) = load(m, x) else {
+                return Flow::Interpret;
+            };
+            m.put_output(value);
+            Flow::Next
+        }),
+        (Instruction::AdjustRelativeBase(_), &[x]) => Box::new(move |m: &mut Intcode| {
+            let Some(base) = load(m, x).and_then(|offset| m.relative_base.checked_add(offset)) else {
+                return Flow::Interpret;
+            };
+            m.relative_base = base;
+            Flow::Next
+        }),
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+fn interpreted() -> Op {
+    Box::new(|_: &mut Intcode| Flow::Interpret)
+}
+
+/*
+    An Intcode machine run through compiled code. Memory is only
+    reachable read-only or through intcode_mut, which throws away
+    everything compiled, so cached code never goes stale.
+*/
+pub struct Jit {
+    intcode: Intcode,
+    blocks: Vec<Option<Arc<Block>>>,
+    // addresses covered by a compiled block
+    code: Vec<bool>,
+    // addresses that have been written over while compiled
+    volatile: Vec<bool>,
+}
+
+impl Jit {
+    pub fn new(intcode: Intcode) -> Jit {
+        Jit { intcode, blocks: Vec::new(), code: Vec::new(), volatile: Vec::new() }
+    }
+
+    pub fn intcode(&self) -> &Intcode {
+        &self.intcode
+    }
+
+    pub fn intcode_mut(&mut self) -> &mut Intcode {
+        self.blocks.clear();
+        self.code.clear();
+        self.volatile.clear();
+        &mut self.intcode
+    }
+
+    pub fn into_inner(self) -> Intcode {
+        self.intcode
+    }
+
+    pub fn add_input(&mut self, value: i64) {
+        self.intcode.add_input(value);
+    }
+
+    pub fn add_inputs<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = i64>,
+    {
+        self.intcode.add_inputs(values);
+    }
+
+    pub fn take_outputs(&mut self) -> Vec<i64> {
+        self.intcode.take_outputs()
+    }
+
+    /*
+        Same contract as Intcode::process.
+    */
+    pub fn process(&mut self) -> Result<State, IntcodeError> {
+        let m = &self.intcode;
+        if m.state == State::Halted {
+            return Ok(m.state);
+        }
+        if !m.breakpoints.is_empty() || !m.watchpoints.is_empty() || m.tracer.is_some() || m.profile.is_some() {
+            return self.intcode.process();
+        }
+
+        self.intcode.state = State::Processing;
+
+        loop {
+            let ip = self.intcode.ip;
+            let stop = if flag(&self.volatile, ip) {
+                self.interpret()?
+            } else {
+                let block = self.block_at(ip);
+                self.run(&block)?
+            };
+
+            if let Some(state) = stop {
+                return Ok(state);
+            }
+        }
+    }
+
+    fn run(&mut self, block: &Block) -> Result<Option<State>, IntcodeError> {
+        for entry in &block.entries {
+            match (entry.op)(&mut self.intcode) {
+                Flow::Next => {}
+                Flow::Wrote(address) => {
+                    if flag(&self.code, address) {
+                        self.invalidate(address);
+                        self.intcode.ip = entry.next;
+                        return Ok(None);
+                    }
+                }
+                Flow::Jump(to) => {
+                    self.intcode.ip = to;
+                    return Ok(None);
+                }
+                Flow::Wait => {
+                    self.intcode.ip = entry.ip;
+                    self.intcode.state = State::InputRequired;
+                    return Ok(Some(State::InputRequired));
+                }
+                Flow::Interpret => {
+                    self.intcode.ip = entry.ip;
+                    return self.interpret();
+                }
+            }
+        }
+
+        self.intcode.ip = block.end;
+        Ok(None)
+    }
+
+    fn interpret(&mut self) -> Result<Option<State>, IntcodeError> {
+        let step = self.intcode.step()?;
+
+        if let Some(w) = step.write {
+            if flag(&self.code, w.address) {
+                self.invalidate(w.address);
+            }
+        }
+
+        match step.state {
+            State::Processing => Ok(None),
+            state => Ok(Some(state)),
+        }
+    }
+
+    fn block_at(&mut self, ip: usize) -> Arc<Block> {
+        if let Some(Some(block)) = self.blocks.get(ip) {
+            return block.clone();
+        }
+
+        let block = Arc::new(self.compile(ip));
+        if ip >= self.blocks.len() {
+            self.blocks.resize(ip + 1, None);
+        }
+        self.blocks[ip] = Some(block.clone());
+        mark(&mut self.code, block.start, block.end);
+
+        block
+    }
+
+    fn compile(&self, start: usize) -> Block {
+        let m = &self.intcode;
+        let mut entries = Vec::new();
+        let mut ip = start;
+
+        while entries.len() < MAX_BLOCK {
+            let instr = process_instruction(m.read(ip));
+            let size = instr.map_or(1, |i| i.size());
+            let next = ip + size;
+
+            if (ip..next).any(|a| flag(&self.volatile, a)) {
+                if entries.is_empty() {
+                    entries.push(Entry { ip, next, op: interpreted() });
+                    ip = next;
+                }
+                break;
+            }
+
+            // running off the end of memory is an error only the interpreter reports
+            let op = match instr {
+                Some(instr) if next < m.instructions.len() => compile_op(m, ip, instr),
+                _ => None,
+            };
+
+            let ends = op.is_none()
+                || matches!(instr, Some(Instruction::JumpIfTrue(..)) | Some(Instruction::JumpIfFalse(..)));
+
+            entries.push(Entry { ip, next, op: op.unwrap_or_else(interpreted) });
+            ip = next;
+
+            if ends {
+                break;
+            }
+        }
+
+        Block { start, end: ip, entries }
+    }
+
+    /*
+        Drops every block holding address and marks the instruction
+        there as one to interpret from now on.
+    */
+    fn invalidate(&mut self, address: usize) {
+        for slot in self.blocks.iter_mut() {
+            let block = match slot {
+                Some(block) if block.start <= address && address < block.end => block.clone(),
+                _ => continue,
+            };
+
+            if let Some(entry) = block.entries.iter().find(|e| e.ip <= address && address < e.next) {
+                mark(&mut self.volatile, entry.ip, entry.next);
+            }
+            *slot = None;
+        }
+
+        self.code.clear();
+        for block in self.blocks.iter().flatten() {
+            mark(&mut self.code, block.start, block.end);
+        }
+    }
+}
+
+fn flag(flags: &[bool], address: usize) -> bool {
+    flags.get(address).copied().unwrap_or(false)
+}
+
+fn mark(flags: &mut Vec<bool>, start: usize, end: usize) {
+    if end > flags.len() {
+        flags.resize(end, false);
+    }
+    for f in &mut flags[start..end] {
+        *f = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::adder;
+
+    // the day 5 comparison program: 999 below 8, 1000 at 8, 1001 above
+    const COMPARE: [i64; 47] = [
+        3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,
+        1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99,
+    ];
+
+    fn programs() -> Vec<(Vec<i64>, Vec<i64>)> {
+        vec![
+            (vec![1,9,10,3,2,3,11,0,99,30,40,50], vec![]),
+            (vec![1002,4,3,4,33], vec![]),
+            (vec![3,0,4,0,99], vec![42]),
+            (vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], vec![0]),
+            (vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], vec![5]),
+            (vec![1105,-1,4,99,104,7,99], vec![]),
+            (COMPARE.to_vec(), vec![7]),
+            (COMPARE.to_vec(), vec![8]),
+            (COMPARE.to_vec(), vec![9]),
+            (vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], vec![]),
+            (vec![1102,34915192,34915192,7,4,7,99,0], vec![]),
+            (vec![104,1125899906842624,99], vec![]),
+            (vec![109,19,204,-15,21101,3,4,1,99,0,0,0,0,0,0,0,0,0,0,0,0], vec![]),
+            (vec![1,20,21,22, 1005,23,18, 1101,0,2,0, 1101,1,0,23, 1105,1,0, 99, 0, 6,7,0,0], vec![]),
+            // errors
+            (vec![1,0,0,0,42,99], vec![]),
+            (vec![11101,1,1,5,99], vec![]),
+            (vec![1101,2,3,1000000000,99], vec![]),
+            (vec![109,-5,204,0,99], vec![]),
+            (vec![1101,1,1,5], vec![]),
+            (vec![1105,1,50,99], vec![]),
+            (vec![1101,9223372036854775807,1,0,99], vec![]),
+            (vec![1102,4611686018427387904,2,0,99], vec![]),
+            (vec![109,9223372036854775807,109,1,99], vec![]),
+            (vec![109,9223372036854775807,204,1,99], vec![]),
+            // waits for a second input
+            (adder(), vec![1]),
+        ]
+    }
+
+    /*
+        Runs program on both engines with the same inputs, resuming
+        whenever they wait for input, and checks they agree throughout.
+    */
+    fn compare(program: &[i64], inputs: &[i64]) {
+        let mut interpreter = Intcode::new(program.to_vec());
+        let mut jit = Jit::new(Intcode::new(program.to_vec()));
+        let mut inputs = inputs.iter();
+
+        loop {
+            let expected = interpreter.process();
+            let actual = jit.process();
+
+            assert_eq!(actual, expected, "{:?}", program);
+            assert_eq!(jit.take_outputs(), interpreter.take_outputs(), "{:?}", program);
+            assert_eq!(jit.intcode().memory(), interpreter.memory(), "{:?}", program);
+            assert_eq!(jit.intcode().get_ip(), interpreter.get_ip(), "{:?}", program);
+            assert_eq!(jit.intcode().get_relative_base(), interpreter.get_relative_base(), "{:?}", program);
+
+            match (expected, inputs.next()) {
+                (Ok(State::InputRequired), Some(&value)) => {
+                    interpreter.add_input(value);
+                    jit.add_input(value);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        for (program, inputs) in programs() {
+            compare(&program, &inputs);
+        }
+    }
+
+    #[test]
+    fn self_modifying_loop() {
+        // counts [30] down from 5, bumping the first operand of the add at 8 each time
+        let mut program = vec![
+            1001,30,-1,30,    // 0: [30] -= 1
+            1001,9,1,9,       // 4: [9] += 1
+            1101,0,0,31,      // 8: [31] = #[9] + #0
+            1005,30,0,        // 12: loop while [30] > 0
+            4,31,99,          // 15: out [31]
+        ];
+        program.resize(32, 0);
+        program[30] = 5;
+
+        compare(&program, &[]);
+
+        let mut jit = Jit::new(Intcode::new(program));
+        jit.process().unwrap();
+        assert_eq!(jit.take_outputs(), vec![5]);
+    }
+
+    // xorshift, enough to make varied programs without a dependency
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> i64 {
+            (self.next() % n) as i64
+        }
+    }
+
+    /*
+        Instructions with small operands, reading and writing a scratch
+        area after the code and now and then the code itself.
+    */
+    fn random_program(rng: &mut Rng) -> Vec<i64> {
+        let count = 4 + rng.below(20) as usize;
+        let code_len = count * 4;
+        let mut program = Vec::new();
+
+        for _ in 0..count {
+            let opcode = [1, 2, 3, 4, 5, 6, 7, 8, 9][rng.below(9) as usize];
+            let params = match opcode {
+                1 | 2 | 7 | 8 => 3,
+                5 | 6 => 2,
+                _ => 1,
+            };
+
+            let mut word = opcode;
+            let mut place = 100;
+            let mut words = Vec::new();
+
+            for p in 0..params {
+                let write = p == params - 1 && matches!(opcode, 1 | 2 | 3 | 7 | 8);
+                let mode = if write { [0, 2][rng.below(2) as usize] } else { rng.below(3) };
+                word += mode * place;
+                place *= 10;
+
+                let jump_target = matches!(opcode, 5 | 6) && p == 1;
+                words.push(match mode {
+                    _ if jump_target => rng.below(code_len as u64 + 2),
+                    1 => rng.below(7) - 3,
+                    2 => rng.below(8) - 2,
+                    _ if rng.below(5) == 0 => rng.below(code_len as u64),
+                    _ => code_len as i64 + rng.below(8),
+                });
+            }
+
+            program.push(word);
+            program.extend(words);
+        }
+
+        program.push(99);
+        program.extend((0..8).map(|_| rng.below(7) - 3));
+        program
+    }
+
+    /*
+        Whether the interpreter stops on its own within limit steps;
+        programs that loop forever aren't useful comparisons.
+    */
+    fn terminates(program: &[i64], inputs: &[i64], limit: usize) -> bool {
+        let mut intcode = Intcode::new(program.to_vec());
+        intcode.add_inputs(inputs.iter().copied());
+
+        for _ in 0..limit {
+            match intcode.step() {
+                Ok(step) if step.state == State::Processing => {}
+                _ => return true,
+            }
+        }
+
+        false
+    }
+
+    #[test]
+    fn random_programs_match_interpreter() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut compared = 0;
+
+        for _ in 0..2000 {
+            let program = random_program(&mut rng);
+            let inputs: Vec<i64> = (0..3).map(|_| rng.below(10) - 5).collect();
+
+            if terminates(&program, &inputs, 10_000) {
+                compare(&program, &inputs);
+                compared += 1;
+            }
+        }
+
+        assert!(compared > 1000);
+    }
+}
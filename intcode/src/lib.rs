@@ -1,22 +1,76 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+
+mod error;
+pub mod cluster;
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod disasm;
+pub mod icb;
+pub mod io;
+pub mod jit;
+pub mod load;
+mod memory;
+pub mod network;
+pub mod profile;
+pub mod search;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+
+pub use error::IntcodeError;
+pub use io::{Input, Output};
+pub use profile::Profile;
+pub use snapshot::Snapshot;
+pub use trace::TraceFormat;
+
+use memory::Memory;
+use trace::Tracer;
+
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 20;
 
 pub struct Intcode {
-    instructions: Vec<i64>,
-    output: Option<i64>,
-    input: Vec<i64>,
+    instructions: Memory,
+    // decoded instruction per address, cleared when the word is written
+    decoded: Vec<Option<Instruction>>,
+    decode_cache: bool,
+    output: VecDeque<i64>,
+    last_output: Option<i64>,
+    input: VecDeque<i64>,
+    input_device: Option<Box<dyn Input + Send>>,
+    output_device: Option<Box<dyn Output + Send>>,
+    ip: usize,
+    relative_base: i64,
+    memory_limit: usize,
+    breakpoints: HashSet<usize>,
+    watchpoints: HashMap<usize, WatchKind>,
+    tracer: Option<Tracer>,
+    profile: Option<Profile>,
     state: State
 
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum State {
     Ready,
     Processing,
     InputRequired,
     Halted,
+    Breakpoint(usize),
+    Watch { addr: usize, old: i64, new: i64 },
 }
 
-#[derive(PartialEq, Debug)]
-enum Instruction {
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Instruction {
     Add(char, char, char),
     Multiply(char, char, char),
     Input(char),
@@ -25,9 +79,142 @@ enum Instruction {
     JumpIfFalse(char, char),
     LessThan(char, char, char),
     Equals(char, char, char),
+    AdjustRelativeBase(char),
     Halt
 }
 
+impl Instruction {
+    pub fn opcode(&self) -> i64 {
+        match self {
+            Instruction::Add(..) => 1,
+            Instruction::Multiply(..) => 2,
+            Instruction::Input(..) => 3,
+            Instruction::Output(..) => 4,
+            Instruction::JumpIfTrue(..) => 5,
+            Instruction::JumpIfFalse(..) => 6,
+            Instruction::LessThan(..) => 7,
+            Instruction::Equals(..) => 8,
+            Instruction::AdjustRelativeBase(..) => 9,
+            Instruction::Halt => 99,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Add(..) => "add",
+            Instruction::Multiply(..) => "mul",
+            Instruction::Input(..) => "in",
+            Instruction::Output(..) => "out",
+            Instruction::JumpIfTrue(..) => "jt",
+            Instruction::JumpIfFalse(..) => "jf",
+            Instruction::LessThan(..) => "lt",
+            Instruction::Equals(..) => "eq",
+            Instruction::AdjustRelativeBase(..) => "rbo",
+            Instruction::Halt => "hlt",
+        }
+    }
+
+    /*
+        Parameter modes in parameter order, one per parameter.
+    */
+    pub fn modes(&self) -> Vec<char> {
+        match *self {
+            Instruction::Add(p1, p2, p3) => vec![p1, p2, p3],
+            Instruction::Multiply(p1, p2, p3) => vec![p1, p2, p3],
+            Instruction::Input(p1) => vec![p1],
+            Instruction::Output(p1) => vec![p1],
+            Instruction::JumpIfTrue(p1, p2) => vec![p1, p2],
+            Instruction::JumpIfFalse(p1, p2) => vec![p1, p2],
+            Instruction::LessThan(p1, p2, p3) => vec![p1, p2, p3],
+            Instruction::Equals(p1, p2, p3) => vec![p1, p2, p3],
+            Instruction::AdjustRelativeBase(p1) => vec![p1],
+            Instruction::Halt => vec![],
+        }
+    }
+
+    /*
+        Whether the last parameter is an address the instruction writes to.
+    */
+    pub fn writes(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Add(..) | Instruction::Multiply(..) | Instruction::Input(..)
+                | Instruction::LessThan(..) | Instruction::Equals(..)
+        )
+    }
+
+    /*
+        The instruction word: opcode plus the modes, so that
+        process_instruction(instr.encode()) gives instr back.
+    */
+    pub fn encode(&self) -> i64 {
+        let mut value = self.opcode();
+        let mut place = 100;
+
+        for mode in self.modes() {
+            value += mode.to_digit(10).unwrap_or(0) as i64 * place;
+            place *= 10;
+        }
+
+        value
+    }
+
+    /*
+        Number of words the instruction takes up, opcode included.
+    */
+    pub fn size(&self) -> usize {
+        self.modes().len() + 1
+    }
+}
+
+/*
+    What one instruction read, kept on the stack so that running
+    without a tracer or profile doesn't allocate. An instruction has
+    at most two operands.
+*/
+#[derive(Default)]
+struct Accesses {
+    operands: [i64; 2],
+    operand_count: usize,
+    reads: [usize; 2],
+    read_count: usize,
+}
+
+struct Executed {
+    ip: usize,
+    raw: i64,
+    instruction: Instruction,
+    write: Option<MemoryWrite>,
+    // ip has moved past the end of memory
+    ran_off: bool,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct MemoryWrite {
+    pub address: usize,
+    pub value: i64,
+}
+
+/*
+    What a single call to step() executed.
+
+    operands holds the values read by the instruction in parameter order;
+    the address written by a write parameter is reported in write instead.
+    reads lists the cells read through position or relative operands.
+    state is the machine state once the instruction has run, so an Input
+    that found no value reports State::InputRequired and leaves ip alone.
+*/
+#[derive(PartialEq, Debug, Clone)]
+pub struct Step {
+    pub ip: usize,
+    pub raw: i64,
+    pub instruction: Instruction,
+    pub operands: Vec<i64>,
+    pub reads: Vec<usize>,
+    pub write: Option<MemoryWrite>,
+    pub state: State,
+}
+
 /*
         Instructions
         1 1 0 0 2
@@ -38,178 +225,582 @@ enum Instruction {
         B: 2nd param
         C: 1st param
         D,E: optcode
+        2: relative mode
         1: immediate mode
         0: position mode
 
    */
-fn process_instruction(value: i64) -> Instruction {
+fn process_instruction(value: i64) -> Option<Instruction> {
+    if !(0..100000).contains(&value) {
+        return None;
+    }
 
-    let mut s_chars: Vec<char> = value.to_string().chars().collect();
+    let mode = |place: i64| (b'0' + (value / place % 10) as u8) as char;
+    let (c, b, a) = (mode(100), mode(1000), mode(10000));
+
+    match value % 100 {
+        1 => Some(Instruction::Add(c, b, a)),
+        2 => Some(Instruction::Multiply(c, b, a)),
+        3 => Some(Instruction::Input(c)),
+        4 => Some(Instruction::Output(c)),
+        5 => Some(Instruction::JumpIfTrue(c, b)),
+        6 => Some(Instruction::JumpIfFalse(c, b)),
+        7 => Some(Instruction::LessThan(c, b, a)),
+        8 => Some(Instruction::Equals(c, b, a)),
+        9 => Some(Instruction::AdjustRelativeBase(c)),
+        99 => Some(Instruction::Halt),
+        _ => None,
+    }
+}
 
-    while s_chars.len() < 5 {
-        s_chars.insert(0, '0');
+impl Intcode {
+    pub fn new(instructions: Vec<i64>) -> Intcode {
+        Intcode {
+            instructions: Memory::from(instructions),
+            decoded: Vec::new(),
+            decode_cache: true,
+            output: VecDeque::new(),
+            last_output: None,
+            input: VecDeque::new(),
+            input_device: None,
+            output_device: None,
+            ip: 0,
+            relative_base: 0,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            breakpoints: HashSet::new(),
+            watchpoints: HashMap::new(),
+            tracer: None,
+            profile: None,
+            state: State::Ready,
+        }
     }
 
-    let mut instr = String::new();
+    /*
+        A new machine at exactly this point of execution. Memory pages
+        are shared with this one until either side writes to them, so
+        forking is cheap however large memory has grown.
+
+        Queued inputs and outputs, breakpoints and watchpoints are
+        copied. Devices and traces stay with this machine.
+    */
+    pub fn fork(&self) -> Intcode {
+        Intcode {
+            instructions: self.instructions.clone(),
+            decoded: Vec::new(),
+            decode_cache: self.decode_cache,
+            output: self.output.clone(),
+            last_output: self.last_output,
+            input: self.input.clone(),
+            input_device: None,
+            output_device: None,
+            ip: self.ip,
+            relative_base: self.relative_base,
+            memory_limit: self.memory_limit,
+            breakpoints: self.breakpoints.clone(),
+            watchpoints: self.watchpoints.clone(),
+            tracer: None,
+            profile: None,
+            state: self.state,
+        }
+    }
 
-    instr.push(s_chars[3]);
-    instr.push(s_chars[4]);
+    pub fn get_output(&self) -> Option<i64> {
+        self.last_output()
+    }
 
-    match instr.as_str() {
-        "01" => return Instruction::Add(s_chars[2], s_chars[1], s_chars[0]),
-        "02" => return Instruction::Multiply(s_chars[2], s_chars[1], s_chars[0]),
-        "03" => return Instruction::Input(s_chars[2]),
-        "04" => return Instruction::Output(s_chars[2]),
-        "05" => return Instruction::JumpIfTrue(s_chars[2], s_chars[1]),
-        "06" => return Instruction::JumpIfFalse(s_chars[2], s_chars[1]),
-        "07" => return Instruction::LessThan(s_chars[2], s_chars[1], s_chars[0]),
-        "08" => return Instruction::Equals(s_chars[2], s_chars[1], s_chars[0]),
-        "99" => return Instruction::Halt,
-        _ => panic!("Invalid optcode value."),
+    /*
+        The most recent value written by an Output instruction,
+        even if it has already been drained by take_outputs.
+    */
+    pub fn last_output(&self) -> Option<i64> {
+        self.last_output
     }
-}
 
-fn get_index(instructions: &Vec<i64>, pos: usize, mode: char) -> usize {
-    if mode == '1' {
-        return pos;
+    /*
+        Drains every value output since the last call, oldest first.
+    */
+    pub fn take_outputs(&mut self) -> Vec<i64> {
+        self.output.drain(..).collect()
     }
 
-    instructions[pos] as usize
-}
+    pub fn add_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
 
-impl Intcode {
-    pub fn new(instructions: Vec<i64>) -> Intcode {
-        let input = Vec::new();
-        Intcode {instructions, output: None, input, state: State::Ready}
+    pub fn add_inputs<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = i64>,
+    {
+        self.input.extend(values);
     }
 
-    pub fn get_output(&self) -> Option<i64> {
-        self.output
+    /*
+        Values queued with add_input are always consumed first;
+        the device is only asked once the queue is empty.
+    */
+    pub fn set_input_device<D>(&mut self, device: D)
+    where
+        D: Input + Send + 'static,
+    {
+        self.input_device = Some(Box::new(device));
     }
 
-    pub fn add_input(&mut self, value: i64) {
-        self.input.push(value);
+    /*
+        Once an output device is attached, outputs go to it
+        instead of the queue drained by take_outputs.
+    */
+    pub fn set_output_device<D>(&mut self, device: D)
+    where
+        D: Output + Send + 'static,
+    {
+        self.output_device = Some(Box::new(device));
+    }
+
+    /*
+        Records every instruction executed from now on to writer.
+        Replaces any trace already running, without flushing it.
+    */
+    pub fn start_trace<W>(&mut self, writer: W, format: TraceFormat) -> std::io::Result<()>
+    where
+        W: Write + Send + 'static,
+    {
+        self.tracer = Some(Tracer::new(Box::new(writer), format)?);
+        Ok(())
+    }
+
+    /*
+        Ends tracing, flushing the writer. Reports the first error
+        the trace ran into, if any.
+    */
+    pub fn stop_trace(&mut self) -> std::io::Result<()> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /*
+        Starts counting executions, reads and writes from zero.
+    */
+    pub fn start_profile(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn stop_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
     }
 
     pub fn new_instructions(&mut self, instructions: Vec<i64>) {
-        self.instructions = instructions;
+        self.instructions = Memory::from(instructions);
+        self.decoded.clear();
+        self.ip = 0;
+        self.relative_base = 0;
+        self.state = State::Ready;
+    }
+
+    pub fn get_ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn get_relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /*
+        Caps how far memory may grow past the loaded program.
+        The limit is a number of words, not bytes.
+    */
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.memory_limit = limit;
+    }
+
+    /*
+        Turns the per-address cache of decoded instructions on or off.
+        It is on by default; turning it off decodes every instruction
+        each time it runs, which is mostly useful for benchmarking.
+    */
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = enabled;
+        self.decoded.clear();
+    }
+
+    pub fn get_memory_limit(&self) -> usize {
+        self.memory_limit
     }
 
     pub fn get_value_at(&self, index: usize) -> Option<i64> {
-        if index >= self.instructions.len() {
-            return None;
+        self.instructions.get(index)
+    }
+
+    /*
+        Patches memory from the host, e.g. a puzzle's noun and verb.
+        Memory grows to reach index, up to the memory limit. An index
+        past the limit is refused with AddressOutOfBounds, reported
+        against the instruction at the current ip.
+    */
+    pub fn set_value_at(&mut self, index: usize, value: i64) -> Result<(), IntcodeError> {
+        if index >= self.memory_limit {
+            let ip = self.ip;
+            return Err(IntcodeError::AddressOutOfBounds { ip, instruction: self.read(ip), address: index });
         }
 
-        Some(self.instructions[index])
+        self.write(index, value);
+        Ok(())
+    }
+
+    /*
+        A copy of everything in memory so far: the loaded program
+        plus whatever it has grown to.
+    */
+    pub fn memory(&self) -> Vec<i64> {
+        self.instructions.to_vec()
     }
 
     pub fn get_state(&self) -> &str {
         match self.state {
-            State::Ready => return "ready",
-            State::Processing => return "processing",
+            State::Ready => "ready",
+            State::Processing => "processing",
             State::InputRequired => "inputRequired",
-            State::Halted => return  "halted",
+            State::Halted => "halted",
+            State::Breakpoint(_) => "breakpoint",
+            State::Watch { .. } => "watch",
         }
     }
 
-    pub fn process(&mut self) {
-        let mut pos: usize = 0;
+    /*
+        process() stops with State::Breakpoint(addr) before executing
+        the instruction at addr. Processing again runs it and carries on.
+    */
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn has_breakpoint(&self, addr: usize) -> bool {
+        self.breakpoints.contains(&addr)
+    }
+
+    /*
+        Once an instruction reads or writes addr (as kind asks), it
+        finishes and the machine stops with State::Watch. For reads
+        old and new are the same value.
+    */
+    pub fn add_watchpoint(&mut self, addr: usize, kind: WatchKind) {
+        self.watchpoints.insert(addr, kind);
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    pub fn process(&mut self) -> Result<State, IntcodeError> {
+        if self.state == State::Halted {
+            return Ok(self.state);
+        }
+
+        // resuming from a breakpoint runs the instruction it stopped on
+        let mut resume = self.state == State::Breakpoint(self.ip);
 
         loop {
-            self.state = State::Processing;
-            let instr = process_instruction(self.instructions[pos]);
-            match instr {
-                Instruction::Add(p1,p2,p3) => {
-                    let pos1 = get_index(&self.instructions, pos+1, p1);
-                    let pos2 = get_index(&self.instructions, pos+2, p2);
-                    let pos3 = get_index(&self.instructions, pos+3, p3);
-
-                    self.instructions[pos3] = self.instructions[pos1] + self.instructions[pos2];
-                    pos += 4;
-                },
-                Instruction::Multiply(p1,p2,p3) => {
-                    let pos1 = get_index(&self.instructions, pos+1, p1);
-                    let pos2 = get_index(&self.instructions, pos+2, p2);
-                    let pos3 = get_index(&self.instructions, pos+3, p3);
-
-                    self.instructions[pos3] = self.instructions[pos1] * self.instructions[pos2];
-                    pos += 4;
-
-                },
-                Instruction::Input(p1) => {
-                    if let Some(value) = self.get_input() {
-                        let pos1 = get_index(&self.instructions, pos+1, p1);
-                        self.instructions[pos1] = value;
-                    } else {
-                        panic!("No input!")
-                    }
-                    pos += 2;
-
-                },
-                Instruction::Output(p1) => {
-                    let pos1 = get_index(&self.instructions, pos+1, p1);
-                    self.output = Some(self.instructions[pos1]);
-                    pos += 2;
-
-                },
-                Instruction::JumpIfTrue(p1,p2) => {
-                    let idx1 = get_index(&self.instructions, pos+1, p1);
-                    let idx2 = get_index(&self.instructions, pos+2, p2);
-
-                    if self.instructions[idx1] > 0 {
-                        pos = self.instructions[idx2] as usize;
-                    } else {
-                        pos += 3;
-                    }
-                },
-                Instruction::JumpIfFalse(p1,p2) => {
-                    let idx1 = get_index(&self.instructions, pos+1, p1);
-                    let idx2 = get_index(&self.instructions, pos+2, p2);
-
-                    if self.instructions[idx1] == 0 {
-                        pos = self.instructions[idx2] as usize;
-                    } else {
-                        pos += 3;
-                    }
-                },
-                Instruction::LessThan(p1,p2,p3) => {
-                    let idx1 = get_index(&self.instructions, pos+1, p1);
-                    let idx2 = get_index(&self.instructions, pos+2, p2);
-                    let idx3 = get_index(&self.instructions, pos+3, p3);
-
-                    if self.instructions[idx1] < self.instructions[idx2] {
-                        self.instructions[idx3] = 1;
-                    } else {
-                        self.instructions[idx3] = 0;
-                    }
-                    pos += 4;
-                },
-                Instruction::Equals(p1,p2,p3) => {
-                    let idx1 = get_index(&self.instructions, pos+1, p1);
-                    let idx2 = get_index(&self.instructions, pos+2, p2);
-                    let idx3 = get_index(&self.instructions, pos+3, p3);
-
-                    if self.instructions[idx1] == self.instructions[idx2] {
-                        self.instructions[idx3] = 1;
-                    } else {
-                        self.instructions[idx3] = 0;
-                    }
-                    pos += 4;
-                },
-                Instruction::Halt => {
-                    self.state = State::Halted;
-                    break
-                },
+            if !resume && !self.breakpoints.is_empty() && self.breakpoints.contains(&self.ip) {
+                self.state = State::Breakpoint(self.ip);
+                return Ok(self.state);
+            }
+            resume = false;
+
+            let state = self.step_quietly()?;
+            if state != State::Processing {
+                return Ok(state);
+            }
+        }
+    }
+
+    /*
+        Executes exactly one instruction at ip and reports what it did.
+        Breakpoints are not checked here, watchpoints are.
+    */
+    pub fn step(&mut self) -> Result<Step, IntcodeError> {
+        let mut accesses = Accesses::default();
+        let executed = self.execute(&mut accesses)?;
+
+        let step = Step {
+            ip: executed.ip,
+            raw: executed.raw,
+            instruction: executed.instruction,
+            operands: accesses.operands[..accesses.operand_count].to_vec(),
+            reads: accesses.reads[..accesses.read_count].to_vec(),
+            write: executed.write,
+            state: self.state,
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(&step);
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(&step);
+        }
+
+        if executed.ran_off {
+            return Err(IntcodeError::EndOfMemory { ip: step.ip, instruction: step.raw });
+        }
+
+        Ok(step)
+    }
+
+    /*
+        step() for process(), without building a Step nobody will see.
+    */
+    fn step_quietly(&mut self) -> Result<State, IntcodeError> {
+        if self.tracer.is_some() || self.profile.is_some() {
+            return Ok(self.step()?.state);
+        }
+
+        let executed = self.execute(&mut Accesses::default())?;
+        if executed.ran_off {
+            return Err(IntcodeError::EndOfMemory { ip: executed.ip, instruction: executed.raw });
+        }
+
+        Ok(self.state)
+    }
+
+    fn execute(&mut self, accesses: &mut Accesses) -> Result<Executed, IntcodeError> {
+        self.state = State::Processing;
+        let ip = self.ip;
+        let raw = self.read(ip);
+        let instr = match self.decode(ip, raw) {
+            Some(instr) => instr,
+            None => return Err(IntcodeError::UnknownOpcode { ip, instruction: raw }),
+        };
+
+        let mut write = None;
+
+        match instr {
+            Instruction::Add(p1,p2,p3) => {
+                let a = self.operand(ip, 1, p1, accesses)?;
+                let b = self.operand(ip, 2, p2, accesses)?;
+                let pos3 = self.get_write_index(ip, 3, p3)?;
+
+                let value = a.checked_add(b).ok_or(IntcodeError::Overflow { ip, instruction: raw })?;
+                write = Some(MemoryWrite { address: pos3, value });
+                self.ip = ip + 4;
+            },
+            Instruction::Multiply(p1,p2,p3) => {
+                let a = self.operand(ip, 1, p1, accesses)?;
+                let b = self.operand(ip, 2, p2, accesses)?;
+                let pos3 = self.get_write_index(ip, 3, p3)?;
+
+                let value = a.checked_mul(b).ok_or(IntcodeError::Overflow { ip, instruction: raw })?;
+                write = Some(MemoryWrite { address: pos3, value });
+                self.ip = ip + 4;
+            },
+            Instruction::Input(p1) => {
+                let pos1 = self.get_write_index(ip, 1, p1)?;
+                match self.get_input() {
+                    Some(value) => {
+                        write = Some(MemoryWrite { address: pos1, value });
+                        self.ip = ip + 2;
+                    },
+                    None => self.state = State::InputRequired,
+                }
+            },
+            Instruction::Output(p1) => {
+                let value = self.operand(ip, 1, p1, accesses)?;
+
+                self.put_output(value);
+                self.ip = ip + 2;
+            },
+            Instruction::JumpIfTrue(p1,p2) => {
+                let cond = self.operand(ip, 1, p1, accesses)?;
+                let target = self.operand(ip, 2, p2, accesses)?;
+
+                if cond != 0 {
+                    self.ip = self.jump_target(ip, target)?;
+                } else {
+                    self.ip = ip + 3;
+                }
+            },
+            Instruction::JumpIfFalse(p1,p2) => {
+                let cond = self.operand(ip, 1, p1, accesses)?;
+                let target = self.operand(ip, 2, p2, accesses)?;
+
+                if cond == 0 {
+                    self.ip = self.jump_target(ip, target)?;
+                } else {
+                    self.ip = ip + 3;
+                }
+            },
+            Instruction::LessThan(p1,p2,p3) => {
+                let a = self.operand(ip, 1, p1, accesses)?;
+                let b = self.operand(ip, 2, p2, accesses)?;
+                let idx3 = self.get_write_index(ip, 3, p3)?;
+
+                let value = if a < b { 1 } else { 0 };
+                write = Some(MemoryWrite { address: idx3, value });
+                self.ip = ip + 4;
+            },
+            Instruction::Equals(p1,p2,p3) => {
+                let a = self.operand(ip, 1, p1, accesses)?;
+                let b = self.operand(ip, 2, p2, accesses)?;
+                let idx3 = self.get_write_index(ip, 3, p3)?;
+
+                let value = if a == b { 1 } else { 0 };
+                write = Some(MemoryWrite { address: idx3, value });
+                self.ip = ip + 4;
+            },
+            Instruction::AdjustRelativeBase(p1) => {
+                let offset = self.operand(ip, 1, p1, accesses)?;
+
+                self.relative_base = self.relative_base.checked_add(offset)
+                    .ok_or(IntcodeError::Overflow { ip, instruction: raw })?;
+                self.ip = ip + 2;
+            },
+            Instruction::Halt => self.state = State::Halted,
+        }
+
+        let watching = !self.watchpoints.is_empty();
+        let mut watch = None;
+
+        if watching {
+            for &addr in &accesses.reads[..accesses.read_count] {
+                if matches!(self.watchpoints.get(&addr), Some(WatchKind::Read) | Some(WatchKind::Access)) {
+                    let value = self.read(addr);
+                    watch = Some(State::Watch { addr, old: value, new: value });
+                    break;
+                }
             }
+        }
+
+        if let Some(w) = write {
+            let old = if watching { self.read(w.address) } else { 0 };
+            self.write(w.address, w.value);
 
-            if pos >= self.instructions.len() {
-                panic!("Invalid intcode size!")
+            if watching && matches!(self.watchpoints.get(&w.address), Some(WatchKind::Write) | Some(WatchKind::Access)) {
+                watch = Some(State::Watch { addr: w.address, old, new: w.value });
             }
         }
+
+        let ran_off = self.state == State::Processing && self.ip >= self.instructions.len();
+
+        if let (State::Processing, Some(watch)) = (self.state, watch) {
+            self.state = watch;
+        }
+
+        Ok(Executed { ip, raw, instruction: instr, write, ran_off })
+    }
+
+    fn decode(&mut self, ip: usize, raw: i64) -> Option<Instruction> {
+        if let Some(Some(instr)) = self.decoded.get(ip) {
+            return Some(*instr);
+        }
+
+        let instr = process_instruction(raw)?;
+        if !self.decode_cache {
+            return Some(instr);
+        }
+        if ip >= self.decoded.len() {
+            self.decoded.resize(ip + 1, None);
+        }
+        self.decoded[ip] = Some(instr);
+
+        Some(instr)
+    }
+
+    fn operand(&self, ip: usize, offset: usize, mode: char, accesses: &mut Accesses) -> Result<i64, IntcodeError> {
+        let address = self.get_index(ip, offset, mode)?;
+        if mode != '1' {
+            accesses.reads[accesses.read_count] = address;
+            accesses.read_count += 1;
+        }
+
+        let value = self.read(address);
+        accesses.operands[accesses.operand_count] = value;
+        accesses.operand_count += 1;
+
+        Ok(value)
+    }
+
+    /*
+        Resolves the address of the parameter at ip + offset.
+        Addresses are checked against the memory limit here so
+        reads and writes never have to fail.
+    */
+    fn get_index(&self, ip: usize, offset: usize, mode: char) -> Result<usize, IntcodeError> {
+        // the instruction word is only needed to report an error
+        let instruction = || self.read(ip);
+        let address = match mode {
+            '0' => self.read(ip + offset),
+            '1' => (ip + offset) as i64,
+            '2' => match self.relative_base.checked_add(self.read(ip + offset)) {
+                Some(address) => address,
+                None => return Err(IntcodeError::Overflow { ip, instruction: instruction() }),
+            },
+            _ => return Err(IntcodeError::InvalidMode { ip, instruction: instruction(), mode }),
+        };
+
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress { ip, instruction: instruction(), address });
+        }
+
+        let address = address as usize;
+        if address >= self.memory_limit {
+            return Err(IntcodeError::AddressOutOfBounds { ip, instruction: instruction(), address });
+        }
+
+        Ok(address)
+    }
+
+    fn get_write_index(&self, ip: usize, offset: usize, mode: char) -> Result<usize, IntcodeError> {
+        if mode == '1' {
+            let instruction = self.read(ip);
+            return Err(IntcodeError::InvalidMode { ip, instruction, mode });
+        }
+
+        self.get_index(ip, offset, mode)
+    }
+
+    fn jump_target(&self, ip: usize, target: i64) -> Result<usize, IntcodeError> {
+        if target < 0 {
+            let instruction = self.read(ip);
+            return Err(IntcodeError::NegativeAddress { ip, instruction, address: target });
+        }
+
+        Ok(target as usize)
+    }
+
+    fn read(&self, index: usize) -> i64 {
+        self.instructions.get(index).unwrap_or(0)
+    }
+
+    fn write(&mut self, index: usize, value: i64) {
+        if let Some(cached) = self.decoded.get_mut(index) {
+            *cached = None;
+        }
+        self.instructions.set(index, value);
     }
 
     fn get_input(&mut self) -> Option<i64> {
-        self.input.pop()
+        if let Some(value) = self.input.pop_front() {
+            return Some(value);
+        }
+
+        match self.input_device.as_mut() {
+            Some(device) => device.input(),
+            None => None,
+        }
+    }
+
+    fn put_output(&mut self, value: i64) {
+        match self.output_device.as_mut() {
+            Some(device) => device.output(value),
+            None => self.output.push_back(value),
+        }
+        self.last_output = Some(value);
     }
 }
 
@@ -217,12 +808,17 @@ impl Intcode {
 mod tests {
     use super::*;
 
+    // reads two values and outputs their sum; shared by the other modules' tests
+    pub(crate) fn adder() -> Vec<i64> {
+        vec![3,11,3,12,1,11,12,13,4,13,99,0,0,0]
+    }
+
     #[test]
     fn add_multiply_optcodes() {
         let optcodes = vec![1,1,1,4,99,5,6,0,99];
 
         let mut intcode = Intcode::new(optcodes);
-        intcode.process();
+        intcode.process().unwrap();
 
         assert_eq!(intcode.get_value_at(0), Some(30));
     }
@@ -234,7 +830,7 @@ mod tests {
         let mut intcode = Intcode::new(vec![99,0,0,0]);
 
         intcode.new_instructions(optcodes);
-        intcode.process();
+        intcode.process().unwrap();
 
         assert_eq!(intcode.get_value_at(0), Some(30));
     }
@@ -244,7 +840,7 @@ mod tests {
         let value = 1;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Add('0', '0', '0')));
+        assert!(matches!(ans, Some(Instruction::Add('0', '0', '0'))));
     }
 
     #[test]
@@ -252,7 +848,7 @@ mod tests {
         let value = 10001;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Add('0', '0', '1')));
+        assert!(matches!(ans, Some(Instruction::Add('0', '0', '1'))));
     }
 
     #[test]
@@ -260,14 +856,14 @@ mod tests {
         let value = 2;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Multiply('0', '0', '0')));
+        assert!(matches!(ans, Some(Instruction::Multiply('0', '0', '0'))));
     }
 
     #[test]
     fn process_instruction_multiply_2() {
         let value = 102;
         let ans = process_instruction(value);
-        assert!(matches!(ans, Instruction::Multiply('1', '0', '0')));
+        assert!(matches!(ans, Some(Instruction::Multiply('1', '0', '0'))));
     }
 
     #[test]
@@ -275,14 +871,14 @@ mod tests {
         let value = 11002;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Multiply('0', '1', '1')));
+        assert!(matches!(ans, Some(Instruction::Multiply('0', '1', '1'))));
     }
 
     #[test]
     fn process_instruction_input_mode_0() {
         let value = 3;
         let ans = process_instruction(value);
-        assert!(matches!(ans, Instruction::Input('0')));
+        assert!(matches!(ans, Some(Instruction::Input('0'))));
     }
 
     #[test]
@@ -290,14 +886,14 @@ mod tests {
         let value = 103;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Input('1')));
+        assert!(matches!(ans, Some(Instruction::Input('1'))));
     }
 
     #[test]
     fn process_instruction_output_mode_0() {
         let value = 4;
         let ans = process_instruction(value);
-        assert!(matches!(ans, Instruction::Output('0')));
+        assert!(matches!(ans, Some(Instruction::Output('0'))));
     }
 
     #[test]
@@ -305,7 +901,14 @@ mod tests {
         let value = 104;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Output('1')));
+        assert!(matches!(ans, Some(Instruction::Output('1'))));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        for value in [1, 2, 1002, 21101, 3, 203, 104, 1105, 2106, 1107, 21008, 209, 99] {
+            assert_eq!(process_instruction(value).unwrap().encode(), value);
+        }
     }
 
     #[test]
@@ -313,7 +916,379 @@ mod tests {
         let value = 99;
         let ans = process_instruction(value);
 
-        assert!(matches!(ans, Instruction::Halt));
+        assert!(matches!(ans, Some(Instruction::Halt)));
+    }
+
+    #[test]
+    fn process_instruction_adjust_relative_base() {
+        let value = 9;
+        let ans = process_instruction(value);
+
+        assert!(matches!(ans, Some(Instruction::AdjustRelativeBase('0'))));
+    }
+
+    #[test]
+    fn process_instruction_relative_mode() {
+        let value = 21201;
+        let ans = process_instruction(value);
+
+        assert!(matches!(ans, Some(Instruction::Add('2', '1', '2'))));
+    }
+
+    #[test]
+    fn relative_mode_read_and_write() {
+        let instr = vec![109,19,204,-15,21101,3,4,1,99,0,0,0,0,0,0,0,0,0,0,0,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_relative_base(), 19);
+        assert_eq!(intcode.get_output(), Some(21101));
+        assert_eq!(intcode.get_value_at(20), Some(7));
+    }
+
+    #[test]
+    fn quine_test() {
+        let instr = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+
+        let mut intcode = Intcode::new(instr.clone());
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.take_outputs(), instr);
+        assert_eq!(intcode.get_value_at(100), Some(16));
+    }
+
+    #[test]
+    fn read_past_end_is_zero() {
+        let instr = vec![1,500,501,0,99];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_value_at(0), Some(0));
+    }
+
+    #[test]
+    fn write_past_end_grows_memory() {
+        let instr = vec![1101,2,3,1000,99];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_value_at(1000), Some(5));
+        assert_eq!(intcode.get_value_at(999), Some(0));
+        assert_eq!(intcode.get_value_at(1001), None);
+    }
+
+    #[test]
+    fn write_past_memory_limit() {
+        let instr = vec![1101,2,3,1000,99];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.set_memory_limit(1000);
+
+        assert_eq!(intcode.process(), Err(IntcodeError::AddressOutOfBounds { ip: 0, instruction: 1101, address: 1000 }));
+    }
+
+    #[test]
+    fn host_write_past_memory_limit() {
+        let mut intcode = Intcode::new(vec![1101,2,3,1000,99]);
+
+        assert_eq!(intcode.set_value_at(500, 7), Ok(()));
+        assert_eq!(intcode.get_value_at(500), Some(7));
+        assert_eq!(
+            intcode.set_value_at(1 << 40, 1),
+            Err(IntcodeError::AddressOutOfBounds { ip: 0, instruction: 1101, address: 1 << 40 })
+        );
+        assert_eq!(intcode.memory().len(), 501);
+    }
+
+    #[test]
+    fn rewritten_instruction_is_decoded_again() {
+        // runs the add at 0, turns it into a mul and runs it again
+        let instr = vec![1,20,21,22, 1005,23,18, 1101,0,2,0, 1101,1,0,23, 1105,1,0, 99, 0, 6,7,0,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_value_at(0), Some(2));
+        assert_eq!(intcode.get_value_at(22), Some(42));
+    }
+
+    #[test]
+    fn runs_without_decode_cache() {
+        let mut intcode = Intcode::new(vec![1,20,21,22, 1005,23,18, 1101,0,2,0, 1101,1,0,23, 1105,1,0, 99, 0, 6,7,0,0]);
+        intcode.set_decode_cache(false);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_value_at(22), Some(42));
+        assert!(intcode.decoded.is_empty());
+    }
+
+    #[test]
+    fn fork_runs_independently() {
+        // reads a value and outputs it doubled
+        let mut parent = Intcode::new(vec![3,9,1002,9,2,9,4,9,99,0]);
+        assert_eq!(parent.process(), Ok(State::InputRequired));
+
+        let mut child = parent.fork();
+        parent.add_input(5);
+        child.add_input(21);
+        parent.process().unwrap();
+        child.process().unwrap();
+
+        assert_eq!(parent.get_output(), Some(10));
+        assert_eq!(child.get_output(), Some(42));
+        assert_eq!(parent.get_value_at(9), Some(10));
+    }
+
+    #[test]
+    fn fork_shares_untouched_pages() {
+        let mut program = vec![1101,1,1,5000,99];
+        program.resize(8000, 0);
+
+        let parent = Intcode::new(program);
+        let mut child = parent.fork();
+        child.process().unwrap();
+
+        assert_eq!(child.get_value_at(5000), Some(2));
+        assert_eq!(parent.get_value_at(5000), Some(0));
+        assert_eq!(parent.instructions.shared_pages(&child.instructions), 7);
+    }
+
+    #[test]
+    fn unknown_opcode_error() {
+        let instr = vec![1,0,0,0,42,99];
+
+        let mut intcode = Intcode::new(instr);
+
+        assert_eq!(intcode.process(), Err(IntcodeError::UnknownOpcode { ip: 4, instruction: 42 }));
+    }
+
+    #[test]
+    fn invalid_mode_error() {
+        let instr = vec![1301,0,0,0,99];
+
+        let mut intcode = Intcode::new(instr);
+
+        assert_eq!(intcode.process(), Err(IntcodeError::InvalidMode { ip: 0, instruction: 1301, mode: '3' }));
+    }
+
+    #[test]
+    fn immediate_write_error() {
+        let instr = vec![10001,0,0,0,99];
+
+        let mut intcode = Intcode::new(instr);
+
+        assert_eq!(intcode.process(), Err(IntcodeError::InvalidMode { ip: 0, instruction: 10001, mode: '1' }));
+    }
+
+    #[test]
+    fn negative_address_error() {
+        let instr = vec![4,-3,99];
+
+        let mut intcode = Intcode::new(instr);
+
+        assert_eq!(intcode.process(), Err(IntcodeError::NegativeAddress { ip: 0, instruction: 4, address: -3 }));
+    }
+
+    #[test]
+    fn input_required_pauses() {
+        let instr = vec![3,0,99];
+
+        let mut intcode = Intcode::new(instr);
+
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+        assert_eq!(intcode.get_state(), "inputRequired");
+        assert_eq!(intcode.get_ip(), 0);
+    }
+
+    #[test]
+    fn resume_after_input() {
+        let mut intcode = Intcode::new(adder());
+
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+        intcode.add_input(2);
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+        assert_eq!(intcode.get_ip(), 2);
+        intcode.add_input(3);
+        assert_eq!(intcode.process(), Ok(State::Halted));
+        assert_eq!(intcode.get_output(), Some(5));
+        assert_eq!(intcode.process(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn end_of_memory_error() {
+        let instr = vec![1101,1,1,0];
+
+        let mut intcode = Intcode::new(instr);
+
+        assert_eq!(intcode.process(), Err(IntcodeError::EndOfMemory { ip: 0, instruction: 1101 }));
+    }
+
+    #[test]
+    fn overflow_error() {
+        let mut intcode = Intcode::new(vec![1101,i64::MAX,1,0,99]);
+        assert_eq!(intcode.process(), Err(IntcodeError::Overflow { ip: 0, instruction: 1101 }));
+        assert_eq!(intcode.get_value_at(0), Some(1101));
+
+        let mut intcode = Intcode::new(vec![1102,i64::MIN,-1,0,99]);
+        assert_eq!(intcode.process(), Err(IntcodeError::Overflow { ip: 0, instruction: 1102 }));
+
+        let mut intcode = Intcode::new(vec![109,i64::MAX,109,1,99]);
+        assert_eq!(intcode.process(), Err(IntcodeError::Overflow { ip: 2, instruction: 109 }));
+        assert_eq!(intcode.get_relative_base(), i64::MAX);
+
+        let mut intcode = Intcode::new(vec![109,i64::MAX,204,1,99]);
+        assert_eq!(intcode.process(), Err(IntcodeError::Overflow { ip: 2, instruction: 204 }));
+    }
+
+    #[test]
+    fn inputs_are_fifo() {
+        let instr = vec![3,0,3,1,4,0,4,1,99];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.add_input(7);
+        intcode.add_input(8);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.take_outputs(), vec![7, 8]);
+    }
+
+    #[test]
+    fn add_inputs_and_drain_outputs() {
+        let instr = vec![3,7,4,7,1105,1,0,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.add_inputs(vec![1, 2, 3]);
+
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+        assert_eq!(intcode.take_outputs(), vec![1, 2, 3]);
+        assert_eq!(intcode.take_outputs(), Vec::<i64>::new());
+        assert_eq!(intcode.last_output(), Some(3));
+    }
+
+    #[test]
+    fn large_multiply_test() {
+        let instr = vec![1102,34915192,34915192,7,4,7,99,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_output(), Some(1219070632396864));
+    }
+
+    #[test]
+    fn large_number_test() {
+        let instr = vec![104,1125899906842624,99];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_output(), Some(1125899906842624));
+    }
+
+    #[test]
+    fn step_reports_decoded_instruction() {
+        let instr = vec![1002,4,3,4,33];
+
+        let mut intcode = Intcode::new(instr);
+        let step = intcode.step().unwrap();
+
+        assert_eq!(step.ip, 0);
+        assert_eq!(step.raw, 1002);
+        assert_eq!(step.instruction, Instruction::Multiply('0', '1', '0'));
+        assert_eq!(step.instruction.opcode(), 2);
+        assert_eq!(step.instruction.modes(), vec!['0', '1', '0']);
+        assert_eq!(step.operands, vec![33, 3]);
+        assert_eq!(step.write, Some(MemoryWrite { address: 4, value: 99 }));
+        assert_eq!(step.state, State::Processing);
+        assert_eq!(intcode.get_ip(), 4);
+
+        let step = intcode.step().unwrap();
+        assert_eq!(step.instruction, Instruction::Halt);
+        assert_eq!(step.state, State::Halted);
+    }
+
+    #[test]
+    fn step_waits_for_input() {
+        let instr = vec![3,0,99];
+
+        let mut intcode = Intcode::new(instr);
+        let step = intcode.step().unwrap();
+
+        assert_eq!(step.write, None);
+        assert_eq!(step.state, State::InputRequired);
+        assert_eq!(intcode.get_ip(), 0);
+
+        intcode.add_input(5);
+        let step = intcode.step().unwrap();
+        assert_eq!(step.write, Some(MemoryWrite { address: 0, value: 5 }));
+        assert_eq!(intcode.get_ip(), 2);
+    }
+
+    #[test]
+    fn breakpoint_stops_and_resumes() {
+        let instr = vec![1101,1,1,9,1101,2,2,10,99,0,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.add_breakpoint(4);
+        intcode.add_breakpoint(8);
+
+        assert_eq!(intcode.process(), Ok(State::Breakpoint(4)));
+        assert_eq!(intcode.get_state(), "breakpoint");
+        assert_eq!(intcode.get_value_at(9), Some(2));
+        assert_eq!(intcode.get_value_at(10), Some(0));
+
+        assert_eq!(intcode.process(), Ok(State::Breakpoint(8)));
+        assert_eq!(intcode.get_value_at(10), Some(4));
+
+        assert!(intcode.remove_breakpoint(4));
+        assert_eq!(intcode.process(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn breakpoint_in_loop_fires_each_pass() {
+        let instr = vec![3,7,4,7,1105,1,0,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.add_inputs(vec![1, 2]);
+        intcode.add_breakpoint(2);
+
+        assert_eq!(intcode.process(), Ok(State::Breakpoint(2)));
+        assert_eq!(intcode.process(), Ok(State::Breakpoint(2)));
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+        assert_eq!(intcode.take_outputs(), vec![1, 2]);
+    }
+
+    #[test]
+    fn write_watchpoint() {
+        let instr = vec![1101,1,1,9,1101,2,2,9,99,5];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.add_watchpoint(9, WatchKind::Write);
+
+        assert_eq!(intcode.process(), Ok(State::Watch { addr: 9, old: 5, new: 2 }));
+        assert_eq!(intcode.get_ip(), 4);
+        assert_eq!(intcode.process(), Ok(State::Watch { addr: 9, old: 2, new: 4 }));
+        assert_eq!(intcode.process(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn read_watchpoint() {
+        let instr = vec![1101,1,1,9,4,9,99,0,0,0];
+
+        let mut intcode = Intcode::new(instr);
+        intcode.add_watchpoint(9, WatchKind::Read);
+
+        assert_eq!(intcode.process(), Ok(State::Watch { addr: 9, old: 2, new: 2 }));
+        assert_eq!(intcode.get_ip(), 6);
+        assert_eq!(intcode.get_output(), Some(2));
+
+        assert!(intcode.remove_watchpoint(9));
+        intcode.add_watchpoint(9, WatchKind::Access);
+        intcode.new_instructions(vec![1101,1,1,9,4,9,99,0,0,0]);
+        assert_eq!(intcode.process(), Ok(State::Watch { addr: 9, old: 0, new: 2 }));
     }
 
     #[test]
@@ -322,7 +1297,7 @@ mod tests {
 
         let mut intcode = Intcode::new(instr);
         intcode.add_input(1);
-        intcode.process();
+        intcode.process().unwrap();
 
 
         assert_eq!(intcode.get_output(), Some(1));
@@ -334,19 +1309,27 @@ mod tests {
 
         let mut intcode = Intcode::new(instr);
         intcode.add_input(0);
-        intcode.process();
+        intcode.process().unwrap();
 
 
         assert_eq!(intcode.get_output(), Some(0));
     }
 
+    #[test]
+    fn jump_if_true_on_negative() {
+        let mut intcode = Intcode::new(vec![1105,-1,4,99,104,7,99]);
+        intcode.process().unwrap();
+
+        assert_eq!(intcode.get_output(), Some(7));
+    }
+
     #[test]
     fn e2e_test_1() {
         let instr = vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
 
         let mut intcode = Intcode::new(instr);
         intcode.add_input(2);
-        intcode.process();
+        intcode.process().unwrap();
 
 
         assert_eq!(intcode.get_output(), Some(999));
@@ -358,7 +1341,7 @@ mod tests {
 
         let mut intcode = Intcode::new(instr);
         intcode.add_input(8);
-        intcode.process();
+        intcode.process().unwrap();
 
 
         assert_eq!(intcode.get_output(), Some(1000));
@@ -370,7 +1353,7 @@ mod tests {
 
         let mut intcode = Intcode::new(instr);
         intcode.add_input(34);
-        intcode.process();
+        intcode.process().unwrap();
 
         assert_eq!(intcode.get_output(), Some(1001));
     }
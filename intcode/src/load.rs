@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::Intcode;
+
+/*
+    Reading programs in the usual puzzle-input form: integers separated
+    by commas. Whitespace works as a separator too, so space-separated
+    images load as well, and trailing and repeated commas are ignored.
+*/
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    // both counted from 1
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: not a number: '{}'", self.line, self.column, self.token)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(e: ParseError) -> LoadError {
+        LoadError::Parse(e)
+    }
+}
+
+pub fn parse_program(text: &str) -> Result<Vec<i64>, ParseError> {
+    let mut program = Vec::new();
+
+    for (l, line) in text.lines().enumerate() {
+        let mut rest = line;
+
+        while let Some(start) = rest.find(|c: char| c != ',' && !c.is_whitespace()) {
+            let token = &rest[start..];
+            let len = token.find(|c: char| c == ',' || c.is_whitespace()).unwrap_or(token.len());
+            let token = &token[..len];
+
+            match token.parse::<i64>() {
+                Ok(value) => program.push(value),
+                Err(_) => {
+                    let offset = line.len() - rest.len() + start;
+                    let column = line[..offset].chars().count() + 1;
+                    return Err(ParseError { line: l + 1, column, token: token.to_string() });
+                }
+            }
+            rest = &rest[start + len..];
+        }
+    }
+
+    Ok(program)
+}
+
+impl FromStr for Intcode {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Intcode, ParseError> {
+        Ok(Intcode::new(parse_program(text)?))
+    }
+}
+
+impl Intcode {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Intcode, LoadError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        Ok(text.parse()?)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Intcode, LoadError> {
+        Intcode::from_reader(File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerant_of_layout() {
+        assert_eq!(parse_program("1,0,0,3,99"), Ok(vec![1,0,0,3,99]));
+        assert_eq!(parse_program("1, 0,0,\n3,\r\n 99,\n\n"), Ok(vec![1,0,0,3,99]));
+        assert_eq!(parse_program("104 , -5 ,99 ,"), Ok(vec![104,-5,99]));
+        assert_eq!(parse_program(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn whitespace_separated() {
+        assert_eq!(parse_program("1 2 3"), Ok(vec![1,2,3]));
+        assert_eq!(parse_program("1101  2\t3 0\n99"), Ok(vec![1101,2,3,0,99]));
+        assert_eq!(
+            parse_program("1 2  3x 4"),
+            Err(ParseError { line: 1, column: 6, token: "3x".to_string() })
+        );
+    }
+
+    #[test]
+    fn errors_point_at_token() {
+        assert_eq!(
+            parse_program("1,0,0,3,\n99,  x1 ,3"),
+            Err(ParseError { line: 2, column: 6, token: "x1".to_string() })
+        );
+        assert_eq!(
+            parse_program("1,2.5"),
+            Err(ParseError { line: 1, column: 3, token: "2.5".to_string() })
+        );
+    }
+
+    #[test]
+    fn from_str_and_reader() {
+        let mut intcode: Intcode = "104,7,99\n".parse().unwrap();
+        intcode.process().unwrap();
+        assert_eq!(intcode.get_output(), Some(7));
+
+        let intcode = Intcode::from_reader(&b"1101,2,3,0,99,"[..]).unwrap();
+        assert_eq!(intcode.memory(), vec![1101,2,3,0,99]);
+
+        match Intcode::from_reader(&b"1,2,three"[..]) {
+            Err(LoadError::Parse(e)) => assert_eq!(e.to_string(), "line 1, column 5: not a number: 'three'"),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(matches!(Intcode::from_path("no/such/program.txt"), Err(LoadError::Io(_))));
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+/*
+    Machine memory split into fixed-size pages behind Arcs, so cloning
+    it only copies page pointers. A page is copied the first time it is
+    written while shared, which lets forks of one machine keep sharing
+    everything they haven't touched.
+
+    The last page is padded with zeros past len, and memory never
+    shrinks, so the padding always reads as zero.
+*/
+
+const PAGE_SIZE: usize = 1024;
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Vec<i64>", into = "Vec<i64>")
+)]
+pub(crate) struct Memory {
+    pages: Vec<Arc<Vec<i64>>>,
+    len: usize,
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(words: Vec<i64>) -> Memory {
+        let pages = words
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = chunk.to_vec();
+                page.resize(PAGE_SIZE, 0);
+                Arc::new(page)
+            })
+            .collect();
+
+        Memory { pages, len: words.len() }
+    }
+}
+
+impl From<Memory> for Vec<i64> {
+    fn from(memory: Memory) -> Vec<i64> {
+        memory.to_vec()
+    }
+}
+
+impl Memory {
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<i64> {
+        if index >= self.len {
+            return None;
+        }
+
+        Some(self.pages[index / PAGE_SIZE][index % PAGE_SIZE])
+    }
+
+    /*
+        Writes value at index, growing memory with zeros to reach it.
+    */
+    pub(crate) fn set(&mut self, index: usize, value: i64) {
+        let page = index / PAGE_SIZE;
+
+        while self.pages.len() <= page {
+            self.pages.push(Arc::new(vec![0; PAGE_SIZE]));
+        }
+        if index >= self.len {
+            self.len = index + 1;
+        }
+
+        Arc::make_mut(&mut self.pages[page])[index % PAGE_SIZE] = value;
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<i64> {
+        let mut words: Vec<i64> = self.pages.iter().flat_map(|page| page.iter().copied()).collect();
+        words.truncate(self.len);
+        words
+    }
+
+    /*
+        How many pages are still the same allocation in both.
+    */
+    #[cfg(test)]
+    pub(crate) fn shared_pages(&self, other: &Memory) -> usize {
+        self.pages.iter().zip(&other.pages).filter(|(a, b)| Arc::ptr_eq(a, b)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let words: Vec<i64> = (0..2500).collect();
+        let memory = Memory::from(words.clone());
+
+        assert_eq!(memory.len(), 2500);
+        assert_eq!(memory.get(1024), Some(1024));
+        assert_eq!(memory.get(2500), None);
+        assert_eq!(memory.to_vec(), words);
+    }
+
+    #[test]
+    fn grows_with_zeros() {
+        let mut memory = Memory::from(vec![1, 2, 3]);
+        memory.set(3000, 7);
+
+        assert_eq!(memory.len(), 3001);
+        assert_eq!(memory.get(3), Some(0));
+        assert_eq!(memory.get(2999), Some(0));
+        assert_eq!(memory.get(3000), Some(7));
+    }
+
+    #[test]
+    fn clones_share_until_written() {
+        let mut a = Memory::from(vec![0; 4 * PAGE_SIZE]);
+        let b = a.clone();
+        assert_eq!(a.shared_pages(&b), 4);
+
+        a.set(PAGE_SIZE + 5, 1);
+
+        assert_eq!(a.shared_pages(&b), 3);
+        assert_eq!(a.get(PAGE_SIZE + 5), Some(1));
+        assert_eq!(b.get(PAGE_SIZE + 5), Some(0));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{Intcode, IntcodeError, State};
+
+/*
+    Runs one machine per phase setting, each fed its phase first.
+    The signal starts at 0 and every output of a machine becomes
+    input for the next one.
+
+    With feedback the outputs of the last machine go back to the first
+    and the loop keeps going until the last machine halts. Machines are
+    resumed in turn through State::InputRequired, no threads involved.
+
+    Returns the last value the final machine output, or None if it
+    never produced one.
+*/
+pub fn run_amplifiers(program: &[i64], phases: &[i64], feedback: bool) -> Result<Option<i64>, IntcodeError> {
+    let mut amps: Vec<Intcode> = phases
+        .iter()
+        .map(|phase| {
+            let mut amp = Intcode::new(program.to_vec());
+            amp.add_input(*phase);
+            amp
+        })
+        .collect();
+
+    let mut signals = vec![0];
+    let mut last_signal = None;
+
+    loop {
+        let mut last_state = State::Ready;
+
+        for amp in amps.iter_mut() {
+            amp.add_inputs(signals);
+            last_state = amp.process()?;
+            signals = amp.take_outputs();
+        }
+
+        if let Some(signal) = signals.last() {
+            last_signal = Some(*signal);
+        }
+
+        // no output means nothing can move forward on the next pass either
+        if !feedback || last_state == State::Halted || signals.is_empty() {
+            break;
+        }
+    }
+
+    Ok(last_signal)
+}
+
+/*
+    Tries every ordering of the phase settings and returns the
+    highest thruster signal along with the phases that produced it.
+*/
+pub fn max_thruster_signal(program: &[i64], phases: &[i64], feedback: bool) -> Result<Option<(i64, Vec<i64>)>, IntcodeError> {
+    let mut best: Option<(i64, Vec<i64>)> = None;
+
+    for order in permutations(phases) {
+        if let Some(signal) = run_amplifiers(program, &order, feedback)? {
+            let better = match &best {
+                Some((max, _)) => signal > *max,
+                None => true,
+            };
+            if better {
+                best = Some((signal, order));
+            }
+        }
+    }
+
+    Ok(best)
+}
+
+fn permutations(items: &[i64]) -> Vec<Vec<i64>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+
+    let mut perms = Vec::new();
+
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let first = rest.remove(i);
+
+        for mut perm in permutations(&rest) {
+            perm.insert(0, first);
+            perms.push(perm);
+        }
+    }
+
+    perms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutations_of_three() {
+        let perms = permutations(&[0, 1, 2]);
+
+        assert_eq!(perms.len(), 6);
+        assert_eq!(perms[0], vec![0, 1, 2]);
+        assert_eq!(perms[5], vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn series_chain() {
+        let program = vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
+
+        assert_eq!(run_amplifiers(&program, &[4,3,2,1,0], false), Ok(Some(43210)));
+    }
+
+    #[test]
+    fn series_max_signal() {
+        let program = vec![3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0];
+
+        let best = max_thruster_signal(&program, &[0,1,2,3,4], false).unwrap();
+
+        assert_eq!(best, Some((54321, vec![0,1,2,3,4])));
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let program = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+
+        assert_eq!(run_amplifiers(&program, &[9,8,7,6,5], true), Ok(Some(139629729)));
+    }
+
+    #[test]
+    fn feedback_max_signal() {
+        let program = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+
+        let best = max_thruster_signal(&program, &[5,6,7,8,9], true).unwrap();
+
+        assert_eq!(best, Some((139629729, vec![9,8,7,6,5])));
+    }
+}
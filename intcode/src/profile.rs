@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::disasm;
+use crate::{State, Step};
+
+/*
+    Execution counts gathered while a machine runs with profiling on:
+    how often each address was executed, how often each cell was read
+    or written by an instruction's operands, and how often each kind
+    of instruction ran.
+*/
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Profile {
+    hits: Vec<u64>,
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    opcodes: BTreeMap<&'static str, u64>,
+    total: u64,
+}
+
+fn bump(counts: &mut Vec<u64>, address: usize) {
+    if address >= counts.len() {
+        counts.resize(address + 1, 0);
+    }
+    counts[address] += 1;
+}
+
+fn count(counts: &[u64], address: usize) -> u64 {
+    counts.get(address).copied().unwrap_or(0)
+}
+
+impl Profile {
+    pub(crate) fn record(&mut self, step: &Step) {
+        if step.state == State::InputRequired {
+            return;
+        }
+
+        self.total += 1;
+        bump(&mut self.hits, step.ip);
+        *self.opcodes.entry(step.instruction.mnemonic()).or_insert(0) += 1;
+
+        for &address in &step.reads {
+            bump(&mut self.reads, address);
+        }
+        if let Some(w) = step.write {
+            bump(&mut self.writes, w.address);
+        }
+    }
+
+    // instructions executed in all
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn hits(&self, address: usize) -> u64 {
+        count(&self.hits, address)
+    }
+
+    pub fn reads(&self, address: usize) -> u64 {
+        count(&self.reads, address)
+    }
+
+    pub fn writes(&self, address: usize) -> u64 {
+        count(&self.writes, address)
+    }
+
+    /*
+        Instructions executed per mnemonic, e.g. "add" -> 1200.
+    */
+    pub fn opcodes(&self) -> &BTreeMap<&'static str, u64> {
+        &self.opcodes
+    }
+
+    /*
+        Executed addresses with their hit counts, hottest first.
+    */
+    pub fn hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut spots: Vec<(usize, u64)> =
+            self.hits.iter().enumerate().filter(|(_, &n)| n > 0).map(|(a, &n)| (a, n)).collect();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+
+    fn percent(&self, n: u64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        n as f64 * 100.0 / self.total as f64
+    }
+
+    /*
+        A text report against program (usually the machine's memory):
+        the top hot spots, the opcode histogram, then the disassembly
+        with hits, reads and writes alongside each line.
+    */
+    pub fn report(&self, program: &[i64], top: usize) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "hot spots ({} instructions executed):", self.total);
+        for (address, n) in self.hot_spots().into_iter().take(top) {
+            let text = match disasm::decode_at(program, address) {
+                Some((instr, params)) => disasm::format_instruction(&instr, &params),
+                None => format!("data {}", program.get(address).copied().unwrap_or(0)),
+            };
+            let _ = writeln!(out, "{:>10} {:>5.1}% {:>6}: {}", n, self.percent(n), address, text);
+        }
+
+        let _ = writeln!(out, "\nopcodes:");
+        let mut opcodes: Vec<(&str, u64)> = self.opcodes.iter().map(|(k, v)| (*k, *v)).collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (mnemonic, n) in opcodes {
+            let _ = writeln!(out, "{:>10} {:>5.1}% {}", n, self.percent(n), mnemonic);
+        }
+
+        let _ = writeln!(out, "\n{:>10} {:>8} {:>8}", "hits", "reads", "writes");
+        for line in disasm::disassemble(program) {
+            let columns: Vec<String> = [self.hits(line.address), self.reads(line.address), self.writes(line.address)]
+                .iter()
+                .map(|&n| if n == 0 { String::new() } else { n.to_string() })
+                .collect();
+            let _ = writeln!(out, "{:>10} {:>8} {:>8} {}", columns[0], columns[1], columns[2], line);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Intcode;
+
+    // counts [16] up to 3, outputting each value
+    const COUNTER: [i64; 18] = [1001,16,1,16, 4,16, 1007,16,3,17, 1005,17,0, 99, 0,0, 0,0];
+
+    fn run(program: &[i64]) -> (Intcode, Profile) {
+        let mut intcode = Intcode::new(program.to_vec());
+        intcode.start_profile();
+        intcode.process().unwrap();
+        let profile = intcode.stop_profile().unwrap();
+
+        (intcode, profile)
+    }
+
+    #[test]
+    fn counts_hits_reads_and_writes() {
+        let (mut intcode, profile) = run(&COUNTER);
+
+        assert_eq!(intcode.take_outputs(), vec![1, 2, 3]);
+        assert_eq!(profile.total(), 13);
+        assert_eq!(profile.hits(0), 3);
+        assert_eq!(profile.hits(1), 0);
+        assert_eq!(profile.hits(13), 1);
+        assert_eq!(profile.reads(16), 9);
+        assert_eq!(profile.reads(17), 3);
+        assert_eq!(profile.writes(16), 3);
+        assert_eq!(profile.writes(17), 3);
+        assert_eq!(profile.opcodes().get("add"), Some(&3));
+        assert_eq!(profile.opcodes().get("hlt"), Some(&1));
+    }
+
+    #[test]
+    fn hot_spots_first() {
+        let (_, profile) = run(&COUNTER);
+
+        assert_eq!(profile.hot_spots(), vec![(0, 3), (4, 3), (6, 3), (10, 3), (13, 1)]);
+    }
+
+    #[test]
+    fn report_overlays_disassembly() {
+        let (intcode, profile) = run(&COUNTER);
+        let report = profile.report(&intcode.memory(), 2);
+
+        assert!(report.starts_with(
+            "hot spots (13 instructions executed):\n\
+             \x20        3  23.1%      0: add [16], #1, [16]\n\
+             \x20        3  23.1%      4: out [16]\n"
+        ));
+        assert!(report.contains("opcodes:\n         3  23.1% add\n"));
+        assert!(report.contains("         3                        0: add [16], #1, [16]    ; from 10\n"));
+        assert!(report.contains("                  9        3     16: data 3\n"));
+    }
+}
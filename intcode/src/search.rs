@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::thread;
+
+use crate::symbolic::{Solution, Symbol, Symbolic};
+use crate::{Intcode, IntcodeError, State};
+
+/*
+    The day 2 style of puzzle: patch address 1 (the noun) and address 2
+    (the verb), run the program, and read the result from address 0.
+*/
+
+pub fn run_with_noun_verb(program: &Intcode, noun: i64, verb: i64) -> Result<i64, IntcodeError> {
+    let mut intcode = program.fork();
+    intcode.set_value_at(1, noun)?;
+    intcode.set_value_at(2, verb)?;
+
+    // a forked breakpoint just pauses the run; waiting for input can't finish
+    loop {
+        match intcode.process()? {
+            State::Halted => break,
+            State::InputRequired => {
+                let ip = intcode.get_ip();
+                let instruction = intcode.get_value_at(ip).unwrap_or(0);
+                return Err(IntcodeError::MissingInput { ip, instruction });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(intcode.get_value_at(0).unwrap_or(0))
+}
+
+/*
+    Finds a noun and verb, both 0 to 99, for which the program leaves
+    target at address 0. Pairs that make the program fail, or wait for
+    input, are skipped.
+
+    Address 0 is usually linear in the noun and verb, so the program is
+    first run symbolically and the pair solved for directly. If that
+    finds no pair that checks out, nouns are split between threads, each
+    running forks of program. If several pairs work, the one with the
+    smallest 100 * noun + verb is returned, and threads stop as soon as
+    they can no longer beat the best pair found so far.
+*/
+pub fn solve_noun_verb(program: &Intcode, target: i64) -> Option<(i64, i64)> {
+    // only a checked pair is trusted; the symbolic run can't see failures
+    // that depend on the values, so even Impossible falls back to searching
+    if let Some(Solution::Found(values)) = solve_symbolically(program, target) {
+        let (noun, verb) = (values[&Symbol::Cell(1)], values[&Symbol::Cell(2)]);
+        if run_with_noun_verb(program, noun, verb) == Ok(target) {
+            return Some((noun, verb));
+        }
+    }
+
+    brute_force(program, target)
+}
+
+fn solve_symbolically(program: &Intcode, target: i64) -> Option<Solution> {
+    let mut symbolic = Symbolic::new(program);
+    symbolic.symbolic_cell(1, 0..=99);
+    symbolic.symbolic_cell(2, 0..=99);
+
+    // trying nouns in order and solving for the verb, which is below 100,
+    // gives the smallest 100 * noun + verb
+    match symbolic.run() {
+        Ok(State::Halted) => Some(symbolic.solve_for(&symbolic.value_at(0), target, Symbol::Cell(2))),
+        _ => None,
+    }
+}
+
+fn brute_force(program: &Intcode, target: i64) -> Option<(i64, i64)> {
+    let threads = thread::available_parallelism().map_or(4, |n| n.get()).min(100) as i64;
+    let best = AtomicI64::new(i64::MAX);
+
+    thread::scope(|scope| {
+        for first in 0..threads {
+            // &Intcode can't cross threads since devices aren't Sync, a fork can
+            let base = program.fork();
+            let best = &best;
+
+            scope.spawn(move || {
+                for noun in (first..100).step_by(threads as usize) {
+                    for verb in 0..100 {
+                        let code = 100 * noun + verb;
+                        if code >= best.load(Ordering::Relaxed) {
+                            return;
+                        }
+
+                        if run_with_noun_verb(&base, noun, verb) == Ok(target) {
+                            best.fetch_min(code, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    match best.into_inner() {
+        i64::MAX => None,
+        code => Some((code / 100, code % 100)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_with_patched_cells() {
+        // [0] = [noun] + [verb], then [0] = [0] * [0]
+        let program = Intcode::new(vec![1,0,0,0, 2,0,0,0, 99, 3, 4]);
+
+        assert_eq!(run_with_noun_verb(&program, 9, 10), Ok(49));
+        assert_eq!(program.get_value_at(1), Some(0));
+
+        let mut small = program.fork();
+        small.set_memory_limit(2);
+        assert!(matches!(run_with_noun_verb(&small, 9, 10), Err(IntcodeError::AddressOutOfBounds { address: 2, .. })));
+    }
+
+    #[test]
+    fn finds_smallest_pair() {
+        // [0] = [noun] + [verb], over a table where [n] = n from 5 on
+        let mut table: Vec<i64> = (0..100).collect();
+        table[..5].copy_from_slice(&[1,0,0,0,99]);
+        let program = Intcode::new(table);
+
+        // nouns 0 and 1 read 1 and can't reach 120; noun 2 reads the verb itself
+        assert_eq!(solve_noun_verb(&program, 120), Some((2, 60)));
+        assert_eq!(solve_noun_verb(&program, 500), None);
+    }
+
+    #[test]
+    fn linear_programs_are_solved_directly() {
+        // [0] = 5 * ([1] + 7) + [2]
+        let program = Intcode::new(vec![1,0,0,3, 1001,1,7,3, 1002,3,5,3, 1,3,2,0, 99]);
+
+        assert!(matches!(solve_symbolically(&program, 300), Some(Solution::Found(_))));
+        assert_eq!(solve_noun_verb(&program, 300), Some((34, 95)));
+        assert_eq!(brute_force(&program, 300), Some((34, 95)));
+        assert_eq!(solve_noun_verb(&program, 34), None);
+    }
+
+    #[test]
+    fn repeated_loads_are_not_folded() {
+        // [30] and [31] both read [noun], but [50] is written in between
+        let mut memory = vec![1102,0,0,40, 1001,1,0,13, 1001,1,0,21, 1001,0,0,30, 1101,7,0,50, 1001,0,0,31, 8,30,31,0, 99];
+        memory.resize(100, 0);
+        let program = Intcode::new(memory);
+
+        assert_eq!(run_with_noun_verb(&program, 50, 0), Ok(0));
+        assert_eq!(solve_noun_verb(&program, 0), Some((50, 0)));
+    }
+
+    #[test]
+    fn failing_pairs_are_skipped() {
+        // noun + verb is stored at 4 and executed as the next opcode,
+        // which fails or waits for input for most small sums
+        let program = Intcode::new(vec![1101,0,0,4, 0, 0,0,0, 99]);
+
+        assert_eq!(run_with_noun_verb(&program, 0, 0), Err(IntcodeError::UnknownOpcode { ip: 4, instruction: 0 }));
+        assert_eq!(run_with_noun_verb(&program, 1, 2), Err(IntcodeError::MissingInput { ip: 4, instruction: 3 }));
+        assert_eq!(solve_noun_verb(&program, 1101), Some((0, 99)));
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+use crate::memory::Memory;
+use crate::{Intcode, State};
+
+/*
+    Everything needed to pick a machine up exactly where it was:
+    memory, ip, relative base, the input and output queues and state.
+
+    Attached devices, breakpoints, watchpoints and traces belong to the
+    host rather than the program, so they are not part of a snapshot and
+    restore() leaves them as they are. Memory pages are shared with the
+    machine until one side writes to them, so taking a snapshot is cheap.
+
+    With the serde feature a snapshot can be serialized with any serde
+    format, and save/load write it to disk as JSON.
+*/
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    memory: Memory,
+    ip: usize,
+    relative_base: i64,
+    memory_limit: usize,
+    input: VecDeque<i64>,
+    output: VecDeque<i64>,
+    last_output: Option<i64>,
+    state: State,
+}
+
+impl Snapshot {
+    pub fn memory(&self) -> Vec<i64> {
+        self.memory.to_vec()
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Snapshot {
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(file, self).map_err(std::io::Error::from)
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Snapshot> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        serde_json::from_reader(file).map_err(std::io::Error::from)
+    }
+}
+
+impl Intcode {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.instructions.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            memory_limit: self.memory_limit,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            last_output: self.last_output,
+            state: self.state,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.instructions = snapshot.memory.clone();
+        self.decoded.clear();
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.memory_limit = snapshot.memory_limit;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.last_output = snapshot.last_output;
+        self.state = snapshot.state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::adder;
+
+    #[test]
+    fn branch_from_mid_execution() {
+        let mut intcode = Intcode::new(adder());
+        intcode.add_input(10);
+        assert_eq!(intcode.process(), Ok(State::InputRequired));
+
+        let saved = intcode.snapshot();
+        assert_eq!(saved.ip(), 2);
+        assert_eq!(saved.state(), State::InputRequired);
+
+        let mut results = Vec::new();
+        for second in 1..=3 {
+            intcode.restore(&saved);
+            intcode.add_input(second);
+            intcode.process().unwrap();
+            results.push(intcode.get_output());
+        }
+
+        assert_eq!(results, vec![Some(11), Some(12), Some(13)]);
+    }
+
+    #[test]
+    fn restore_into_fresh_machine() {
+        let mut intcode = Intcode::new(vec![109,5,3,0,99]);
+        intcode.add_input(3);
+        intcode.process().unwrap();
+
+        let mut copy = Intcode::new(Vec::new());
+        copy.restore(&intcode.snapshot());
+
+        assert_eq!(copy.get_relative_base(), 5);
+        assert_eq!(copy.get_state(), "halted");
+        assert_eq!(copy.memory(), intcode.memory());
+        assert_eq!(copy.snapshot(), intcode.snapshot());
+    }
+
+    #[test]
+    fn queues_are_kept() {
+        let mut intcode = Intcode::new(vec![104,7,3,0,99]);
+        intcode.add_inputs(vec![1, 2]);
+        intcode.step().unwrap();
+
+        let saved = intcode.snapshot();
+        intcode.process().unwrap();
+        intcode.take_outputs();
+
+        intcode.restore(&saved);
+        assert_eq!(intcode.take_outputs(), vec![7]);
+        intcode.process().unwrap();
+        assert_eq!(intcode.get_value_at(0), Some(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_and_load() {
+        let mut intcode = Intcode::new(adder());
+        intcode.add_input(20);
+        intcode.process().unwrap();
+
+        let path = std::env::temp_dir().join(format!("intcode-snapshot-{}.json", std::process::id()));
+        intcode.snapshot().save(&path).unwrap();
+        let loaded = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, intcode.snapshot());
+
+        let mut resumed = Intcode::new(Vec::new());
+        resumed.restore(&loaded);
+        resumed.add_input(22);
+        resumed.process().unwrap();
+        assert_eq!(resumed.get_output(), Some(42));
+    }
+}
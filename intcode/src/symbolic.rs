@@ -0,0 +1,786 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::{process_instruction, Instruction, Intcode, IntcodeError, State};
+
+/*
+    Runs a program with some memory cells or inputs left unknown. Values
+    computed from them are kept as expression trees instead of numbers,
+    so once the program halts a cell like day 2's address 0 holds a
+    formula in the unknowns that can be solved for a target.
+
+    Only data may depend on symbols. The run stops with an error when an
+    instruction word, a write address, a jump or the relative base would
+    need a symbol's value. Reading through a symbolic address gives a
+    Load, which is carried along but can't be solved for.
+*/
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub enum Symbol {
+    // the initial value of a memory cell
+    Cell(usize),
+    // the nth symbolic input, counted from 0
+    Input(usize),
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Symbol::Cell(address) => write!(f, "[{}]", address),
+            Symbol::Input(n) => write!(f, "in{}", n),
+        }
+    }
+}
+
+/*
+    Built through add, multiply, less_than and equals, which fold
+    constants and keep anything linear in the symbols in one canonical
+    shape: the terms in symbol order, then the constant.
+*/
+#[derive(PartialEq, Debug, Clone)]
+pub enum Expr {
+    Const(i64),
+    Symbol(Symbol),
+    Add(Rc<Expr>, Rc<Expr>),
+    Multiply(Rc<Expr>, Rc<Expr>),
+    LessThan(Rc<Expr>, Rc<Expr>),
+    Equals(Rc<Expr>, Rc<Expr>),
+    // the value at an address that depends on symbols
+    Load(Rc<Expr>),
+}
+
+/*
+    constant + the sum of coefficient * symbol over terms.
+    Terms never have a coefficient of 0. Arithmetic that overflows
+    gives None, as an overflow does in the interpreter.
+*/
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Linear {
+    pub constant: i64,
+    pub terms: BTreeMap<Symbol, i64>,
+}
+
+impl Linear {
+    fn plus(mut self, other: &Linear) -> Option<Linear> {
+        self.constant = self.constant.checked_add(other.constant)?;
+        for (&symbol, &coefficient) in &other.terms {
+            let sum = self.terms.get(&symbol).unwrap_or(&0).checked_add(coefficient)?;
+            if sum == 0 {
+                self.terms.remove(&symbol);
+            } else {
+                self.terms.insert(symbol, sum);
+            }
+        }
+
+        Some(self)
+    }
+
+    fn times(mut self, factor: i64) -> Option<Linear> {
+        if factor == 0 {
+            return Some(Linear::default());
+        }
+
+        self.constant = self.constant.checked_mul(factor)?;
+        for coefficient in self.terms.values_mut() {
+            *coefficient = coefficient.checked_mul(factor)?;
+        }
+
+        Some(self)
+    }
+
+    fn to_expr(&self) -> Rc<Expr> {
+        let mut expr: Option<Rc<Expr>> = None;
+        let mut push = |term: Rc<Expr>| {
+            expr = Some(match expr.take() {
+                Some(sum) => Rc::new(Expr::Add(sum, term)),
+                None => term,
+            });
+        };
+
+        for (&symbol, &coefficient) in &self.terms {
+            let term = Rc::new(Expr::Symbol(symbol));
+            match coefficient {
+                1 => push(term),
+                _ => push(Rc::new(Expr::Multiply(Rc::new(Expr::Const(coefficient)), term))),
+            }
+        }
+        if self.constant != 0 || self.terms.is_empty() {
+            push(Rc::new(Expr::Const(self.constant)));
+        }
+
+        expr.unwrap()
+    }
+}
+
+impl Expr {
+    pub fn constant(&self) -> Option<i64> {
+        match *self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /*
+        Whether the value depends on memory read through a symbolic
+        address. Two such reads can differ even when they look the same,
+        since memory may have been written in between.
+    */
+    pub fn has_load(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Symbol(_) => false,
+            Expr::Add(a, b) | Expr::Multiply(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                a.has_load() || b.has_load()
+            }
+            Expr::Load(_) => true,
+        }
+    }
+
+    /*
+        The expression as a linear function of the symbols, if it is one.
+    */
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(value) => Some(Linear { constant: *value, terms: BTreeMap::new() }),
+            Expr::Symbol(symbol) => Some(Linear { constant: 0, terms: BTreeMap::from([(*symbol, 1)]) }),
+            Expr::Add(a, b) => a.linear()?.plus(&b.linear()?),
+            Expr::Multiply(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                if a.terms.is_empty() {
+                    b.times(a.constant)
+                } else if b.terms.is_empty() {
+                    a.times(b.constant)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /*
+        The value with symbols replaced by values, or None if a symbol
+        is missing, the expression contains a Load or it overflows.
+    */
+    pub fn eval(&self, values: &BTreeMap<Symbol, i64>) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Symbol(symbol) => values.get(symbol).copied(),
+            Expr::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?),
+            Expr::Multiply(a, b) => a.eval(values)?.checked_mul(b.eval(values)?),
+            Expr::LessThan(a, b) => Some((a.eval(values)? < b.eval(values)?) as i64),
+            Expr::Equals(a, b) => Some((a.eval(values)? == b.eval(values)?) as i64),
+            Expr::Load(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let factor = |expr: &Expr| match expr {
+            Expr::Add(..) => format!("({})", expr),
+            _ => expr.to_string(),
+        };
+
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Symbol(symbol) => write!(f, "{}", symbol),
+            Expr::Add(a, b) => write!(f, "{} + {}", a, b),
+            Expr::Multiply(a, b) => write!(f, "{} * {}", factor(a), factor(b)),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+pub fn add(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+    match a.linear().zip(b.linear()).and_then(|(x, y)| x.plus(&y)) {
+        Some(sum) => sum.to_expr(),
+        None => Rc::new(Expr::Add(a, b)),
+    }
+}
+
+pub fn multiply(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+    let product = match (a.linear(), b.linear()) {
+        (Some(x), Some(y)) if x.terms.is_empty() => y.times(x.constant),
+        (Some(x), Some(y)) if y.terms.is_empty() => x.times(y.constant),
+        _ => None,
+    };
+
+    match product {
+        Some(product) => product.to_expr(),
+        None => Rc::new(Expr::Multiply(a, b)),
+    }
+}
+
+// the constant a - b, when the symbols cancel out
+fn difference(a: &Expr, b: &Expr) -> Option<i64> {
+    let diff = a.linear()?.plus(&b.linear()?.times(-1)?)?;
+    if diff.terms.is_empty() {
+        Some(diff.constant)
+    } else {
+        None
+    }
+}
+
+pub fn less_than(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+    match (a.constant(), b.constant(), difference(&a, &b)) {
+        (Some(x), Some(y), _) => Rc::new(Expr::Const((x < y) as i64)),
+        (_, _, Some(diff)) => Rc::new(Expr::Const((diff < 0) as i64)),
+        _ => Rc::new(Expr::LessThan(a, b)),
+    }
+}
+
+pub fn equals(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+    match (a.constant(), b.constant(), difference(&a, &b)) {
+        (Some(x), Some(y), _) => Rc::new(Expr::Const((x == y) as i64)),
+        (_, _, Some(diff)) => Rc::new(Expr::Const((diff == 0) as i64)),
+        _ if a == b && !a.has_load() => Rc::new(Expr::Const(1)),
+        _ => Rc::new(Expr::Equals(a, b)),
+    }
+}
+
+// whether op on two constants would overflow, as it does in the interpreter
+fn overflows(a: &Expr, b: &Expr, op: fn(i64, i64) -> Option<i64>) -> bool {
+    matches!((a.constant(), b.constant()), (Some(x), Some(y)) if op(x, y).is_none())
+}
+
+/*
+    expr == target, for when it can't be solved here.
+*/
+#[derive(PartialEq, Debug, Clone)]
+pub struct Constraint {
+    pub expr: Expr,
+    pub target: i64,
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} == {}", self.expr, self.target)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Solution {
+    // a value for every symbol, those the target doesn't depend on at their lowest
+    Found(BTreeMap<Symbol, i64>),
+    // linear, but no values within the bounds reach the target
+    Impossible,
+    Unsolved(Constraint),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum SymbolicError {
+    Intcode(IntcodeError),
+    SymbolicInstruction { ip: usize, word: Expr },
+    // a write address, jump target or relative base offset
+    SymbolicAddress { ip: usize, address: Expr },
+    SymbolicBranch { ip: usize, condition: Expr },
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Intcode(e) => write!(f, "{}", e),
+            SymbolicError::SymbolicInstruction { ip, word } => {
+                write!(f, "instruction at {} depends on symbols: {}", ip, word)
+            }
+            SymbolicError::SymbolicAddress { ip, address } => {
+                write!(f, "address used at {} depends on symbols: {}", ip, address)
+            }
+            SymbolicError::SymbolicBranch { ip, condition } => {
+                write!(f, "branch at {} depends on symbols: {}", ip, condition)
+            }
+        }
+    }
+}
+
+impl Error for SymbolicError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SymbolicError::Intcode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<IntcodeError> for SymbolicError {
+    fn from(e: IntcodeError) -> SymbolicError {
+        SymbolicError::Intcode(e)
+    }
+}
+
+// most combinations of the other symbols solve() will try
+const MAX_TRIES: i128 = 1 << 24;
+
+pub struct Symbolic {
+    intcode: Intcode,
+    // cells holding symbolic values; everything else is in intcode
+    cells: BTreeMap<usize, Rc<Expr>>,
+    bounds: BTreeMap<Symbol, RangeInclusive<i64>>,
+    input: VecDeque<Rc<Expr>>,
+    inputs: usize,
+    output: Vec<Expr>,
+}
+
+impl Symbolic {
+    /*
+        Starts from a fork of program, keeping its memory, ip and
+        any inputs already queued.
+    */
+    pub fn new(program: &Intcode) -> Symbolic {
+        let mut intcode = program.fork();
+        let input = intcode.input.drain(..).map(|value| Rc::new(Expr::Const(value))).collect();
+
+        Symbolic {
+            intcode,
+            cells: BTreeMap::new(),
+            bounds: BTreeMap::new(),
+            input,
+            inputs: 0,
+            output: Vec::new(),
+        }
+    }
+
+    /*
+        Replaces the value at address with a symbol ranging over range.
+    */
+    pub fn symbolic_cell(&mut self, address: usize, range: RangeInclusive<i64>) -> Symbol {
+        let symbol = Symbol::Cell(address);
+        self.bounds.insert(symbol, range);
+        self.cells.insert(address, Rc::new(Expr::Symbol(symbol)));
+
+        symbol
+    }
+
+    /*
+        Queues an input that will be a symbol ranging over range.
+    */
+    pub fn symbolic_input(&mut self, range: RangeInclusive<i64>) -> Symbol {
+        let symbol = Symbol::Input(self.inputs);
+        self.inputs += 1;
+        self.bounds.insert(symbol, range);
+        self.input.push_back(Rc::new(Expr::Symbol(symbol)));
+
+        symbol
+    }
+
+    pub fn add_input(&mut self, value: i64) {
+        self.input.push_back(Rc::new(Expr::Const(value)));
+    }
+
+    pub fn take_outputs(&mut self) -> Vec<Expr> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn value_at(&self, address: usize) -> Expr {
+        (*self.value(address)).clone()
+    }
+
+    pub fn get_ip(&self) -> usize {
+        self.intcode.ip
+    }
+
+    /*
+        Runs until the program halts or needs more input.
+    */
+    pub fn run(&mut self) -> Result<State, SymbolicError> {
+        loop {
+            let state = self.step()?;
+            if state != State::Processing {
+                return Ok(state);
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<State, SymbolicError> {
+        let ip = self.intcode.ip;
+        let raw = match self.value(ip).constant() {
+            Some(raw) => raw,
+            None => return Err(SymbolicError::SymbolicInstruction { ip, word: self.value_at(ip) }),
+        };
+        let instr = match process_instruction(raw) {
+            Some(instr) => instr,
+            None => return Err(IntcodeError::UnknownOpcode { ip, instruction: raw }.into()),
+        };
+
+        match instr {
+            Instruction::Add(p1, p2, p3) => {
+                let (a, b) = (self.operand(ip, 1, p1)?, self.operand(ip, 2, p2)?);
+                let index = self.write_index(ip, 3, p3)?;
+                if overflows(&a, &b, i64::checked_add) {
+                    return Err(IntcodeError::Overflow { ip, instruction: raw }.into());
+                }
+                self.store(index, add(a, b));
+            },
+            Instruction::Multiply(p1, p2, p3) => {
+                let (a, b) = (self.operand(ip, 1, p1)?, self.operand(ip, 2, p2)?);
+                let index = self.write_index(ip, 3, p3)?;
+                if overflows(&a, &b, i64::checked_mul) {
+                    return Err(IntcodeError::Overflow { ip, instruction: raw }.into());
+                }
+                self.store(index, multiply(a, b));
+            },
+            Instruction::Input(p1) => {
+                let index = self.write_index(ip, 1, p1)?;
+                match self.input.pop_front() {
+                    Some(value) => self.store(index, value),
+                    None => return Ok(State::InputRequired),
+                }
+            },
+            Instruction::Output(p1) => {
+                let value = self.operand(ip, 1, p1)?;
+                self.output.push((*value).clone());
+            },
+            Instruction::JumpIfTrue(p1, p2) | Instruction::JumpIfFalse(p1, p2) => {
+                let cond = self.operand(ip, 1, p1)?;
+                let taken = match (instr, cond.constant()) {
+                    (Instruction::JumpIfTrue(..), Some(value)) => value != 0,
+                    (_, Some(value)) => value == 0,
+                    (_, None) => return Err(SymbolicError::SymbolicBranch { ip, condition: (*cond).clone() }),
+                };
+
+                if taken {
+                    let target = self.operand(ip, 2, p2)?;
+                    self.intcode.ip = match target.constant() {
+                        Some(target) if target < 0 => {
+                            return Err(IntcodeError::NegativeAddress { ip, instruction: raw, address: target }.into())
+                        }
+                        Some(target) => target as usize,
+                        None => return Err(SymbolicError::SymbolicAddress { ip, address: (*target).clone() }),
+                    };
+                    return self.check_ip(ip, raw);
+                }
+            },
+            Instruction::LessThan(p1, p2, p3) => {
+                let value = less_than(self.operand(ip, 1, p1)?, self.operand(ip, 2, p2)?);
+                self.store(self.write_index(ip, 3, p3)?, value);
+            },
+            Instruction::Equals(p1, p2, p3) => {
+                let value = equals(self.operand(ip, 1, p1)?, self.operand(ip, 2, p2)?);
+                self.store(self.write_index(ip, 3, p3)?, value);
+            },
+            Instruction::AdjustRelativeBase(p1) => {
+                let offset = self.operand(ip, 1, p1)?;
+                match offset.constant() {
+                    Some(offset) => {
+                        self.intcode.relative_base = self.intcode.relative_base.checked_add(offset)
+                            .ok_or(IntcodeError::Overflow { ip, instruction: raw })?;
+                    }
+                    None => return Err(SymbolicError::SymbolicAddress { ip, address: (*offset).clone() }),
+                }
+            },
+            Instruction::Halt => return Ok(State::Halted),
+        }
+
+        self.intcode.ip = ip + instr.size();
+        self.check_ip(ip, raw)
+    }
+
+    fn check_ip(&self, ip: usize, raw: i64) -> Result<State, SymbolicError> {
+        if self.intcode.ip >= self.intcode.instructions.len() {
+            return Err(IntcodeError::EndOfMemory { ip, instruction: raw }.into());
+        }
+
+        Ok(State::Processing)
+    }
+
+    fn value(&self, address: usize) -> Rc<Expr> {
+        match self.cells.get(&address) {
+            Some(expr) => expr.clone(),
+            None => Rc::new(Expr::Const(self.intcode.read(address))),
+        }
+    }
+
+    fn store(&mut self, address: usize, value: Rc<Expr>) {
+        match value.constant() {
+            Some(value) => {
+                self.cells.remove(&address);
+                self.intcode.write(address, value);
+            }
+            None => {
+                // keeps memory as long as it would be after a concrete write
+                self.intcode.write(address, 0);
+                self.cells.insert(address, value);
+            }
+        }
+    }
+
+    // the address a parameter refers to, still symbolic if the parameter is
+    fn address(&self, ip: usize, offset: usize, mode: char) -> Result<Rc<Expr>, SymbolicError> {
+        let param = self.value(ip + offset);
+        match mode {
+            '0' => Ok(param),
+            '2' => match param.constant() {
+                Some(offset) => match self.intcode.relative_base.checked_add(offset) {
+                    Some(address) => Ok(Rc::new(Expr::Const(address))),
+                    None => Err(IntcodeError::Overflow { ip, instruction: self.intcode.read(ip) }.into()),
+                },
+                None => Ok(add(Rc::new(Expr::Const(self.intcode.relative_base)), param)),
+            },
+            _ => {
+                let instruction = self.intcode.read(ip);
+                Err(IntcodeError::InvalidMode { ip, instruction, mode }.into())
+            }
+        }
+    }
+
+    fn check_address(&self, ip: usize, address: i64) -> Result<usize, IntcodeError> {
+        let instruction = self.intcode.read(ip);
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress { ip, instruction, address });
+        }
+
+        let address = address as usize;
+        if address >= self.intcode.memory_limit {
+            return Err(IntcodeError::AddressOutOfBounds { ip, instruction, address });
+        }
+
+        Ok(address)
+    }
+
+    fn operand(&self, ip: usize, offset: usize, mode: char) -> Result<Rc<Expr>, SymbolicError> {
+        if mode == '1' {
+            return Ok(self.value(ip + offset));
+        }
+
+        let address = self.address(ip, offset, mode)?;
+        match address.constant() {
+            Some(address) => Ok(self.value(self.check_address(ip, address)?)),
+            None => Ok(Rc::new(Expr::Load(address))),
+        }
+    }
+
+    fn write_index(&self, ip: usize, offset: usize, mode: char) -> Result<usize, SymbolicError> {
+        let address = self.address(ip, offset, mode)?;
+        match address.constant() {
+            Some(address) => Ok(self.check_address(ip, address)?),
+            None => Err(SymbolicError::SymbolicAddress { ip, address: (*address).clone() }),
+        }
+    }
+
+    pub fn range(&self, symbol: Symbol) -> RangeInclusive<i64> {
+        self.bounds.get(&symbol).cloned().unwrap_or(i64::MIN..=i64::MAX)
+    }
+
+    /*
+        Values for the symbols, within their ranges, that make expr equal
+        target. Linear expressions are solved for the symbol with the
+        widest range, the last one in symbol order if several are as wide,
+        while the others are tried in increasing order. The solution found
+        has the smallest values for the other symbols, in symbol order.
+        Anything else, or too many combinations to try, is handed back as
+        a Constraint.
+    */
+    pub fn solve(&self, expr: &Expr, target: i64) -> Solution {
+        self.solve_with(expr, target, None)
+    }
+
+    /*
+        solve, but solving for symbol when expr depends on it.
+    */
+    pub fn solve_for(&self, expr: &Expr, target: i64, symbol: Symbol) -> Solution {
+        self.solve_with(expr, target, Some(symbol))
+    }
+
+    fn solve_with(&self, expr: &Expr, target: i64, symbol: Option<Symbol>) -> Solution {
+        let unsolved = || Solution::Unsolved(Constraint { expr: expr.clone(), target });
+        let linear = match expr.linear() {
+            Some(linear) => linear,
+            None => return unsolved(),
+        };
+
+        let mut values: BTreeMap<Symbol, i64> = self.bounds.iter().map(|(&s, range)| (s, *range.start())).collect();
+        let mut terms: Vec<(Symbol, i64)> = linear.terms.into_iter().collect();
+        let width = |symbol: Symbol| {
+            let range = self.range(symbol);
+            *range.end() as i128 - *range.start() as i128 + 1
+        };
+
+        let chosen = terms.iter().position(|&(s, _)| Some(s) == symbol);
+        let widest = terms.iter().enumerate().max_by_key(|(_, &(s, _))| (width(s), s)).map(|(i, _)| i);
+        let free = match chosen.or(widest) {
+            Some(i) => terms.remove(i),
+            None if linear.constant == target => return Solution::Found(values),
+            None => return Solution::Impossible,
+        };
+
+        let tries = terms.iter().try_fold(1i128, |tries, &(s, _)| Some(tries * width(s)).filter(|&t| t <= MAX_TRIES));
+        if tries.is_none() {
+            return unsolved();
+        }
+
+        let remainder = target as i128 - linear.constant as i128;
+        if self.assign(free, &terms, remainder, &mut values) {
+            Solution::Found(values)
+        } else {
+            Solution::Impossible
+        }
+    }
+
+    fn assign(&self, free: (Symbol, i64), rest: &[(Symbol, i64)], remainder: i128, values: &mut BTreeMap<Symbol, i64>) -> bool {
+        match rest.split_first() {
+            None => {
+                let (symbol, coefficient) = (free.0, free.1 as i128);
+                let range = self.range(symbol);
+                if remainder % coefficient != 0 {
+                    return false;
+                }
+
+                let value = remainder / coefficient;
+                if value < *range.start() as i128 || value > *range.end() as i128 {
+                    return false;
+                }
+
+                values.insert(symbol, value as i64);
+                true
+            }
+            Some((&(symbol, coefficient), rest)) => {
+                for value in self.range(symbol) {
+                    values.insert(symbol, value);
+                    if self.assign(free, rest, remainder - coefficient as i128 * value as i128, values) {
+                        return true;
+                    }
+                }
+
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn linear_forms_are_canonical() {
+        let x = Rc::new(Expr::Symbol(Symbol::Cell(1)));
+        let y = Rc::new(Expr::Symbol(Symbol::Input(0)));
+        let three = Rc::new(Expr::Const(3));
+
+        let expr = add(multiply(add(y.clone(), x.clone()), three.clone()), multiply(x.clone(), Rc::new(Expr::Const(-3))));
+        assert_eq!(expr.to_string(), "3 * in0");
+        assert_eq!(add(expr.clone(), three.clone()).to_string(), "3 * in0 + 3");
+
+        let square = multiply(x.clone(), x.clone());
+        assert_eq!(square.linear(), None);
+        assert_eq!(multiply(add(x.clone(), three.clone()), y.clone()).to_string(), "([1] + 3) * in0");
+
+        assert_eq!(less_than(add(x.clone(), three.clone()), x.clone()), Rc::new(Expr::Const(0)));
+        assert_eq!(equals(square.clone(), square.clone()), Rc::new(Expr::Const(1)));
+        let load = Rc::new(Expr::Load(x.clone()));
+        assert!(matches!(*equals(load.clone(), load.clone()), Expr::Equals(..)));
+        assert_eq!(equals(x.clone(), y.clone()).to_string(), "([1] == in0)");
+
+        // coefficients that would overflow leave the tree as it is
+        let big = multiply(x.clone(), Rc::new(Expr::Const(i64::MAX)));
+        assert_eq!(add(big.clone(), big.clone()).linear(), None);
+    }
+
+    #[test]
+    fn solves_day_two_style_program() {
+        // [0] = 5 * ([1] + 7) + [2]; the first add reads through [1] and [2]
+        // but its result is overwritten before anything uses it
+        let program = Intcode::new(vec![1,0,0,3, 1001,1,7,3, 1002,3,5,3, 1,3,2,0, 99]);
+
+        let mut symbolic = Symbolic::new(&program);
+        let noun = symbolic.symbolic_cell(1, 0..=99);
+        let verb = symbolic.symbolic_cell(2, 0..=99);
+        assert_eq!(symbolic.run(), Ok(State::Halted));
+
+        let result = symbolic.value_at(0);
+        assert_eq!(result.to_string(), "5 * [1] + [2] + 35");
+
+        let values = match symbolic.solve(&result, 300) {
+            Solution::Found(values) => values,
+            other => panic!("expected a solution, got {:?}", other),
+        };
+        assert_eq!((values[&noun], values[&verb]), (34, 95));
+        assert_eq!(result.eval(&values), Some(300));
+
+        assert_eq!(symbolic.solve(&result, 34), Solution::Impossible);
+
+        // solving for the noun instead tries verbs from 0
+        let values = match symbolic.solve_for(&result, 300, noun) {
+            Solution::Found(values) => values,
+            other => panic!("expected a solution, got {:?}", other),
+        };
+        assert_eq!((values[&noun], values[&verb]), (53, 0));
+    }
+
+    #[test]
+    fn symbolic_inputs_and_outputs() {
+        let program = Intcode::new(asm::assemble("
+            in [a]
+            in [b]
+            lt [a], [b], [c]
+            mul [a], [b], [d]
+            out [c]
+            out [d]
+            hlt
+a:          db 0
+b:          db 0
+c:          db 0
+d:          db 0
+").unwrap());
+
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.add_input(4);
+        symbolic.symbolic_input(0..=9);
+        assert_eq!(symbolic.run(), Ok(State::Halted));
+
+        let outputs = symbolic.take_outputs();
+        assert_eq!(outputs.iter().map(|e| e.to_string()).collect::<Vec<_>>(), vec!["(4 < in0)", "4 * in0"]);
+
+        assert!(matches!(symbolic.solve(&outputs[1], 28), Solution::Found(v) if v[&Symbol::Input(0)] == 7));
+        assert_eq!(
+            symbolic.solve(&outputs[0], 1),
+            Solution::Unsolved(Constraint { expr: outputs[0].clone(), target: 1 })
+        );
+        assert_eq!(Constraint { expr: outputs[0].clone(), target: 1 }.to_string(), "(4 < in0) == 1");
+    }
+
+    #[test]
+    fn stops_where_symbols_decide() {
+        // reading through a symbol is fine, branching on one isn't
+        let program = Intcode::new(vec![1, 0, 0, 0, 4, 0, 99]);
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_cell(1, 0..=99);
+        assert_eq!(symbolic.run(), Ok(State::Halted));
+        assert_eq!(symbolic.take_outputs()[0].to_string(), "[[1]] + 1");
+
+        let program = Intcode::new(vec![1005, 7, 0, 99, 0, 0, 0, 0]);
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_cell(7, 0..=1);
+        assert_eq!(
+            symbolic.run(),
+            Err(SymbolicError::SymbolicBranch { ip: 0, condition: Expr::Symbol(Symbol::Cell(7)) })
+        );
+
+        let program = Intcode::new(vec![1102, 0, 2, 5, 99, 0]);
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_cell(1, 0..=9);
+        assert_eq!(symbolic.run(), Ok(State::Halted));
+        assert_eq!(symbolic.value_at(5).to_string(), "2 * [1]");
+
+        // constants overflow as they would in the interpreter
+        let program = Intcode::new(vec![1102, i64::MAX, 2, 5, 99, 0]);
+        assert_eq!(
+            Symbolic::new(&program).run(),
+            Err(SymbolicError::Intcode(IntcodeError::Overflow { ip: 0, instruction: 1102 }))
+        );
+
+        let program = Intcode::new(vec![1101, 0, 0, 4, 0, 99]);
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_cell(1, 0..=9);
+        assert_eq!(
+            symbolic.run(),
+            Err(SymbolicError::SymbolicInstruction { ip: 4, word: Expr::Symbol(Symbol::Cell(1)) })
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::io::{self, Read, Write};
+
+use crate::{process_instruction, Instruction, MemoryWrite, State, Step};
+
+/*
+    Execution traces, one record per executed instruction.
+
+    Json writes one JSON object per line, handy for reading and for
+    diffing two runs with ordinary text tools. Binary is smaller and
+    can be read back with read_binary for offline replay:
+
+        header:  "ICTR" then a version byte
+        record:  ip u64, raw i64, operand count u8, operands i64...,
+                 write flag u8, then address u64 and value i64 if set
+
+    All numbers are little-endian.
+*/
+
+const MAGIC: &[u8; 4] = b"ICTR";
+const VERSION: u8 = 1;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TraceFormat {
+    Json,
+    Binary,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TraceRecord {
+    pub ip: usize,
+    pub raw: i64,
+    pub instruction: Instruction,
+    pub operands: Vec<i64>,
+    pub write: Option<MemoryWrite>,
+}
+
+impl From<&Step> for TraceRecord {
+    fn from(step: &Step) -> TraceRecord {
+        TraceRecord {
+            ip: step.ip,
+            raw: step.raw,
+            instruction: step.instruction,
+            operands: step.operands.clone(),
+            write: step.write,
+        }
+    }
+}
+
+impl TraceRecord {
+    pub fn to_json(&self) -> String {
+        let modes: Vec<String> = self.instruction.modes().iter().map(|m| m.to_string()).collect();
+        let operands: Vec<String> = self.operands.iter().map(|v| v.to_string()).collect();
+        let write = match self.write {
+            Some(w) => format!("{{\"address\":{},\"value\":{}}}", w.address, w.value),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"ip\":{},\"raw\":{},\"op\":\"{}\",\"modes\":[{}],\"operands\":[{}],\"write\":{}}}",
+            self.ip,
+            self.raw,
+            self.instruction.mnemonic(),
+            modes.join(","),
+            operands.join(","),
+            write
+        )
+    }
+
+    fn write_binary(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(&(self.ip as u64).to_le_bytes())?;
+        w.write_all(&self.raw.to_le_bytes())?;
+        w.write_all(&[self.operands.len() as u8])?;
+        for value in &self.operands {
+            w.write_all(&value.to_le_bytes())?;
+        }
+
+        match self.write {
+            Some(mw) => {
+                w.write_all(&[1])?;
+                w.write_all(&(mw.address as u64).to_le_bytes())?;
+                w.write_all(&mw.value.to_le_bytes())?;
+            }
+            None => w.write_all(&[0])?,
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) struct Tracer {
+    writer: Box<dyn Write + Send>,
+    format: TraceFormat,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub(crate) fn new(mut writer: Box<dyn Write + Send>, format: TraceFormat) -> io::Result<Tracer> {
+        if format == TraceFormat::Binary {
+            writer.write_all(MAGIC)?;
+            writer.write_all(&[VERSION])?;
+        }
+
+        Ok(Tracer { writer, format, error: None })
+    }
+
+    /*
+        Records a step that actually ran. The first write error is kept
+        for finish() and ends tracing, the program itself carries on.
+    */
+    pub(crate) fn record(&mut self, step: &Step) {
+        if self.error.is_some() || step.state == State::InputRequired {
+            return;
+        }
+
+        let record = TraceRecord::from(step);
+        let result = match self.format {
+            TraceFormat::Json => writeln!(self.writer, "{}", record.to_json()),
+            TraceFormat::Binary => record.write_binary(&mut self.writer),
+        };
+
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        self.writer.flush()
+    }
+}
+
+fn read_exact_or_eof(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated trace record")),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(true)
+}
+
+fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_i64(reader: &mut dyn Read) -> io::Result<i64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(i64::from_le_bytes(buf))
+}
+
+fn read_u8(reader: &mut dyn Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/*
+    Reads back a trace written in TraceFormat::Binary.
+*/
+pub fn read_binary<R: Read>(mut reader: R) -> io::Result<Vec<TraceRecord>> {
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid("not an intcode trace"));
+    }
+    if header[4] != VERSION {
+        return Err(invalid("unsupported trace version"));
+    }
+
+    let mut records = Vec::new();
+
+    loop {
+        let mut ip = [0; 8];
+        if !read_exact_or_eof(&mut reader, &mut ip)? {
+            break;
+        }
+
+        let raw = read_i64(&mut reader)?;
+        let instruction = process_instruction(raw).ok_or_else(|| invalid("bad instruction in trace"))?;
+
+        let count = read_u8(&mut reader)?;
+        let mut operands = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            operands.push(read_i64(&mut reader)?);
+        }
+
+        let write = match read_u8(&mut reader)? {
+            0 => None,
+            _ => {
+                let address = read_u64(&mut reader)? as usize;
+                let value = read_i64(&mut reader)?;
+                Some(MemoryWrite { address, value })
+            }
+        };
+
+        records.push(TraceRecord { ip: u64::from_le_bytes(ip) as usize, raw, instruction, operands, write });
+    }
+
+    Ok(records)
+}
+
+/*
+    Index of the first record where two traces differ, or where the
+    shorter one ends. None if they are identical.
+*/
+pub fn first_difference(a: &[TraceRecord], b: &[TraceRecord]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(i) => Some(i),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Intcode;
+    use std::sync::{Arc, Mutex};
+
+    // a Write the test can still look at after handing it to the machine
+    #[derive(Clone, Default)]
+    struct Sink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(program: Vec<i64>, inputs: Vec<i64>, format: TraceFormat) -> Vec<u8> {
+        let sink = Sink::default();
+
+        let mut intcode = Intcode::new(program);
+        intcode.add_inputs(inputs);
+        intcode.start_trace(sink.clone(), format).unwrap();
+        intcode.process().unwrap();
+        intcode.stop_trace().unwrap();
+
+        let bytes = sink.0.lock().unwrap().clone();
+        bytes
+    }
+
+    #[test]
+    fn json_lines() {
+        let bytes = trace(vec![1002,4,3,4,33], vec![], TraceFormat::Json);
+
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "{\"ip\":0,\"raw\":1002,\"op\":\"mul\",\"modes\":[0,1,0],\"operands\":[33,3],\"write\":{\"address\":4,\"value\":99}}\n\
+             {\"ip\":4,\"raw\":99,\"op\":\"hlt\",\"modes\":[],\"operands\":[],\"write\":null}\n"
+        );
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let program = vec![3,9,8,9,10,9,4,9,99,-1,8];
+        let bytes = trace(program, vec![8], TraceFormat::Binary);
+        let records = read_binary(&bytes[..]).unwrap();
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].instruction, Instruction::Input('0'));
+        assert_eq!(records[0].write, Some(MemoryWrite { address: 9, value: 8 }));
+        assert_eq!(records[1].operands, vec![8, 8]);
+        assert_eq!(records[2].operands, vec![1]);
+        assert_eq!(records[3].instruction, Instruction::Halt);
+    }
+
+    #[test]
+    fn diff_two_runs() {
+        let program = vec![3,9,8,9,10,9,4,9,99,-1,8];
+        let a = read_binary(&trace(program.clone(), vec![8], TraceFormat::Binary)[..]).unwrap();
+        let b = read_binary(&trace(program, vec![7], TraceFormat::Binary)[..]).unwrap();
+
+        assert_eq!(first_difference(&a, &a), None);
+        assert_eq!(first_difference(&a, &b), Some(0));
+        assert_eq!(first_difference(&a, &a[..2]), Some(2));
+    }
+
+    #[test]
+    fn waiting_for_input_is_not_recorded() {
+        let bytes = trace(vec![3,0,99], vec![], TraceFormat::Binary);
+
+        assert_eq!(read_binary(&bytes[..]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn rejects_foreign_data() {
+        assert!(read_binary(&b"NOPE\x01"[..]).is_err());
+    }
+}